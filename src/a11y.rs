@@ -0,0 +1,74 @@
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::STORAGE;
+
+/// The latest accessibility report of each page is stored under this prefix.
+const KEY_PREFIX: &str = "a11y/";
+
+/// The accessibility violations of a page, as reported by axe-core in the browser.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct Report {
+    url: String,
+    violations: Vec<Violation>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Violation {
+    id: String,
+    impact: Option<String>,
+    help: String,
+    #[serde(default)]
+    help_url: Option<String>,
+    #[serde(default)]
+    nodes: Vec<serde_json::Value>,
+}
+
+pub(crate) async fn list_reports() -> Result<Json<Vec<Report>>, StatusCode> {
+    let storage = STORAGE.get().unwrap();
+    let read_reports = || -> std::io::Result<Vec<Report>> {
+        let mut reports = vec![];
+        for key in storage.keys(KEY_PREFIX)? {
+            if let Some(report) = storage.get(&key)? {
+                reports.push(serde_json::from_slice(&report)?);
+            }
+        }
+        Ok(reports)
+    };
+
+    read_reports().map(Json).map_err(|err| {
+        log::error!("Failed to read the accessibility reports: {}", err);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+pub(crate) async fn receive_report(Json(report): Json<Report>) -> StatusCode {
+    if report.violations.is_empty() {
+        log::info!("[A11Y] {}: no violations", report.url);
+    } else {
+        log::warn!(
+            "[A11Y] {}: {} violations",
+            report.url,
+            report.violations.len()
+        );
+        for violation in &report.violations {
+            log::warn!(
+                "[A11Y]   {} {}: {} ({} elements)",
+                violation.impact.as_deref().unwrap_or("unknown"),
+                violation.id,
+                violation.help,
+                violation.nodes.len()
+            );
+        }
+    }
+
+    let storage = STORAGE.get().unwrap();
+    let key = format!("{KEY_PREFIX}{}", report.url);
+    if let Err(err) = storage.put(&key, &serde_json::to_vec(&report).unwrap()) {
+        log::error!("Failed to save the accessibility report: {}", err);
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    StatusCode::NO_CONTENT
+}
@@ -0,0 +1,96 @@
+use axum::{
+    body::Body,
+    extract::{Path, Request},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+    Json, Router,
+};
+
+use crate::global::Global;
+use crate::manage::{delete_file, rename_file};
+use crate::server::internal_err;
+use crate::static_files::{get_embedded_file, set_override, EMBEDDED_FILES};
+use crate::watcher::broadcast;
+
+pub(crate) static ADMIN_TOKEN: Global<String> = Global::new();
+
+/// The admin API, which is only available when a token is configured.
+pub(crate) fn admin_router() -> Router {
+    Router::new()
+        .route("/assets", get(list_assets))
+        .route(
+            "/assets/*name",
+            get(get_asset).put(put_asset).delete(delete_asset),
+        )
+        .route("/files/*path", delete(delete_file).patch(rename_file))
+        .layer(middleware::from_fn(authenticate))
+}
+
+async fn authenticate(req: Request, next: Next) -> Response {
+    let Some(token) = ADMIN_TOKEN.get() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let authorized = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|value| constant_time_eq(value.as_bytes(), token.as_bytes()));
+
+    if !authorized {
+        let mut headers = HeaderMap::new();
+        headers.append(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        return (StatusCode::UNAUTHORIZED, headers).into_response();
+    }
+
+    next.run(req).await
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn list_assets() -> Json<&'static [&'static str]> {
+    Json(EMBEDDED_FILES)
+}
+
+async fn get_asset(Path(name): Path<String>) -> (StatusCode, HeaderMap, Body) {
+    match get_embedded_file(&name).await {
+        Some(Ok(content)) => (StatusCode::OK, HeaderMap::new(), Body::from(content)),
+        Some(Err(err)) => internal_err(err),
+        None => (StatusCode::NOT_FOUND, HeaderMap::new(), Body::empty()),
+    }
+}
+
+async fn put_asset(Path(name): Path<String>, content: String) -> StatusCode {
+    if !EMBEDDED_FILES.contains(&name.as_str()) {
+        return StatusCode::NOT_FOUND;
+    }
+
+    if let Err(err) = set_override(&name, Some(content)) {
+        log::error!("Failed to save {}: {}", name, err);
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+    log::info!("[ADMIN] Replaced {}", name);
+    broadcast(&[]).await;
+
+    StatusCode::NO_CONTENT
+}
+
+async fn delete_asset(Path(name): Path<String>) -> StatusCode {
+    if !EMBEDDED_FILES.contains(&name.as_str()) {
+        return StatusCode::NOT_FOUND;
+    }
+
+    if let Err(err) = set_override(&name, None) {
+        log::error!("Failed to save {}: {}", name, err);
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+    log::info!("[ADMIN] Restored {}", name);
+    broadcast(&[]).await;
+
+    StatusCode::NO_CONTENT
+}
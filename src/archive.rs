@@ -0,0 +1,71 @@
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use zip::{
+    result::{ZipError, ZipResult},
+    ZipArchive,
+};
+
+/// The zip archive served as the root, opened once and reopened when it is modified
+/// or when the server is restarted with another archive.
+/// Opening reads the central directory, which keeps the index of the entries in memory.
+static ROOT_ARCHIVE: Mutex<Option<RootArchive>> = Mutex::new(None);
+
+struct RootArchive {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    archive: ZipArchive<File>,
+    /// The directory containing all the entries, e.g. `site/`, as exported sites often have.
+    base: String,
+}
+
+impl RootArchive {
+    fn open(path: &Path) -> ZipResult<Self> {
+        let file = File::open(path)?;
+        let modified = file.metadata()?.modified().ok();
+        let archive = ZipArchive::new(file)?;
+
+        let base = {
+            let mut names = archive.file_names();
+            match names.next().and_then(|name| name.split_once('/')) {
+                Some((dir, _)) if names.all(|name| name.starts_with(&format!("{dir}/"))) => {
+                    format!("{dir}/")
+                }
+                _ => String::new(),
+            }
+        };
+
+        Ok(RootArchive {
+            path: path.to_path_buf(),
+            modified,
+            archive,
+            base,
+        })
+    }
+}
+
+/// Read the file at the URL path from inside the zip archive, or `None` if it is not there.
+pub(crate) fn read_from_archive(path: &Path, url_path: &str) -> ZipResult<Option<Vec<u8>>> {
+    let mut root = ROOT_ARCHIVE.lock().unwrap();
+    let modified = std::fs::metadata(path)?.modified().ok();
+    let root = match root.as_mut() {
+        Some(archive) if archive.path == path && archive.modified == modified => archive,
+        _ => root.insert(RootArchive::open(path)?),
+    };
+
+    let name = format!("{}{}", root.base, url_path.trim_start_matches('/'));
+    let mut file = match root.archive.by_name(&name) {
+        Ok(file) if file.is_file() => file,
+        Ok(_) | Err(ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    let mut content = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut content)?;
+    Ok(Some(content))
+}
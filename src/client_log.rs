@@ -0,0 +1,63 @@
+use std::net::SocketAddr;
+
+use axum::{extract::ConnectInfo, http::StatusCode, Json};
+use serde::Deserialize;
+
+use crate::OPTIONS;
+
+/// An uncaught error or unhandled promise rejection, as reported by the browser.
+#[derive(Debug, Deserialize)]
+pub(crate) struct ClientError {
+    /// The URL of the page where the error happened.
+    url: String,
+    message: String,
+    /// The script URL, line and column, if known.
+    #[serde(default)]
+    source: Option<String>,
+    #[serde(default)]
+    line: Option<u32>,
+    #[serde(default)]
+    column: Option<u32>,
+    #[serde(default)]
+    stack: Option<String>,
+}
+
+/// Log the error reported by the script of [`Listener::client_log`](crate::Listener::client_log),
+/// which is not found unless enabled.
+pub(crate) async fn receive_error(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(error): Json<ClientError>,
+) -> StatusCode {
+    if !OPTIONS.get().unwrap().client_log {
+        return StatusCode::NOT_FOUND;
+    }
+    let location = match (&error.source, error.line, error.column) {
+        (Some(source), Some(line), Some(column)) => {
+            format!(" ({}:{line}:{column})", printable(source))
+        }
+        (Some(source), _, _) => format!(" ({})", printable(source)),
+        _ => String::new(),
+    };
+    log::error!(
+        "[BROWSER] {} {}: {}{}",
+        addr.ip(),
+        printable(&error.url),
+        printable(&error.message),
+        location
+    );
+    if let Some(stack) = &error.stack {
+        for line in stack.lines() {
+            log::error!("[BROWSER]   {}", printable(line.trim()));
+        }
+    }
+
+    StatusCode::NO_CONTENT
+}
+
+/// Replace the control characters sent by the page, such as the line breaks which could
+/// forge other log lines, with spaces.
+fn printable(text: &str) -> String {
+    text.chars()
+        .map(|char| if char.is_control() { ' ' } else { char })
+        .collect()
+}
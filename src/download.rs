@@ -0,0 +1,204 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use axum::{
+    body::Body,
+    extract::Query,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+};
+use chrono::{Datelike, Local, Timelike};
+use flate2::{write::GzEncoder, Compression};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use tokio_util::io::{ReaderStream, SyncIoBridge};
+use walkdir::{DirEntry, WalkDir};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::server::query_path;
+use crate::{path_to_string_but_readable, util::has_hidden_component, OPTIONS};
+
+/// The size of the buffer between the thread writing the archive and the response.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ArchiveQuery {
+    /// The URL path of the directory, e.g. `docs`, or the root when left out.
+    #[serde(default)]
+    path: String,
+    /// The format of the archive, `zip` by default or `tar.gz`.
+    #[serde(default)]
+    format: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Format {
+    Zip,
+    TarGz,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Zip => "zip",
+            Format::TarGz => "tar.gz",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Format::Zip => "application/zip",
+            Format::TarGz => "application/gzip",
+        }
+    }
+}
+
+/// Stream an archive of the directory at `?path=`, like the download link of its listing
+/// but for scripts too, e.g. `curl -o docs.zip localhost:8080/_live-server/archive?path=docs`
+/// or `curl localhost:8080/_live-server/archive?path=docs&format=tar.gz | tar xz`.
+pub(crate) async fn archive(Query(query): Query<ArchiveQuery>) -> (StatusCode, HeaderMap, Body) {
+    let format = match query.format.as_deref() {
+        None | Some("zip") => Format::Zip,
+        Some("tar.gz" | "tgz") => Format::TarGz,
+        Some(_) => {
+            let body = Body::from("The supported formats are zip and tar.gz.");
+            return (StatusCode::BAD_REQUEST, HeaderMap::new(), body);
+        }
+    };
+
+    let Some(dir) = query_path(&query.path).filter(|dir| dir.is_dir()) else {
+        return (StatusCode::NOT_FOUND, HeaderMap::new(), Body::empty());
+    };
+    let options = OPTIONS.get().unwrap();
+    serve_archive(dir, options.show_dotfiles && !options.deny_dotfiles, format)
+}
+
+/// Stream an archive of the directory, so that large trees are not held in memory.
+pub(crate) fn serve_archive(
+    dir: PathBuf,
+    show_dotfiles: bool,
+    format: Format,
+) -> (StatusCode, HeaderMap, Body) {
+    let name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "archive".to_string());
+
+    let (reader, writer) = tokio::io::duplex(BUFFER_SIZE);
+    tokio::task::spawn_blocking(move || {
+        let writer = SyncIoBridge::new(writer);
+        let result = match format {
+            Format::Zip => write_zip(&dir, show_dotfiles, writer),
+            Format::TarGz => write_tar_gz(&dir, show_dotfiles, writer),
+        };
+        if let Err(err) = result {
+            log::error!(
+                "Failed to write the {} archive of {:?}: {}",
+                format.extension(),
+                dir,
+                err
+            );
+        }
+    });
+
+    let mut headers = HeaderMap::new();
+    headers.append(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(format.content_type()),
+    );
+    let disposition = format!(
+        "attachment; filename*=UTF-8''{}.{}",
+        utf8_percent_encode(&name, NON_ALPHANUMERIC),
+        format.extension()
+    );
+    headers.append(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&disposition).unwrap(),
+    );
+
+    (
+        StatusCode::OK,
+        headers,
+        Body::from_stream(ReaderStream::new(reader)),
+    )
+}
+
+fn write_zip<W: Write>(
+    dir: &Path,
+    show_dotfiles: bool,
+    writer: W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut zip = ZipWriter::new_stream(writer);
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .large_file(true);
+
+    for entry in entries(dir, show_dotfiles) {
+        let entry = entry?;
+        let name = path_to_string_but_readable(entry.path().strip_prefix(dir)?);
+        // Symlinks are skipped, since they may point outside of the root.
+        if entry.file_type().is_dir() {
+            zip.add_directory(name, options)?;
+        } else if entry.file_type().is_file() {
+            let modified = entry.metadata()?.modified().ok().and_then(zip_time);
+            let options = match modified {
+                Some(modified) => options.last_modified_time(modified),
+                None => options,
+            };
+            zip.start_file(name, options)?;
+            std::io::copy(&mut std::fs::File::open(entry.path())?, &mut zip)?;
+        }
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+fn write_tar_gz<W: Write>(
+    dir: &Path,
+    show_dotfiles: bool,
+    writer: W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut tar = tar::Builder::new(GzEncoder::new(writer, Compression::default()));
+    tar.follow_symlinks(false);
+
+    for entry in entries(dir, show_dotfiles) {
+        let entry = entry?;
+        let name = path_to_string_but_readable(entry.path().strip_prefix(dir)?);
+        // Symlinks are skipped, as in the zip archives.
+        if entry.file_type().is_dir() {
+            tar.append_dir(name, entry.path())?;
+        } else if entry.file_type().is_file() {
+            tar.append_path_with_name(entry.path(), name)?;
+        }
+    }
+
+    tar.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// The entries under the directory, without the dotfiles unless they are shown.
+fn entries(
+    dir: &Path,
+    show_dotfiles: bool,
+) -> impl Iterator<Item = walkdir::Result<DirEntry>> + '_ {
+    WalkDir::new(dir)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(move |entry| show_dotfiles || !has_hidden_component(dir, entry.path()))
+}
+
+fn zip_time(time: SystemTime) -> Option<zip::DateTime> {
+    let time: chrono::DateTime<Local> = time.into();
+    zip::DateTime::from_date_and_time(
+        time.year().try_into().ok()?,
+        time.month() as u8,
+        time.day() as u8,
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second() as u8,
+    )
+    .ok()
+}
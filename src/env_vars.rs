@@ -0,0 +1,33 @@
+use std::{borrow::Cow, env, sync::LazyLock};
+
+use regex::{Captures, Regex};
+
+/// Only the variables meant for the pages are substituted, so that the secrets of the
+/// environment, e.g. `AWS_SECRET_ACCESS_KEY`, cannot end up in them.
+const PREFIX: &str = "PUBLIC_";
+
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{\s*env:([A-Za-z_][A-Za-z0-9_]*)\s*\}\}").unwrap());
+
+/// Replace the `{{ env:PUBLIC_NAME }}` placeholders with the environment variables. The
+/// ones which are not set or lack the prefix are left as they are, so that they stand out
+/// in the page.
+pub(crate) fn substitute(text: &str) -> Cow<'_, str> {
+    PLACEHOLDER.replace_all(text, |caps: &Captures| {
+        if !caps[1].starts_with(PREFIX) {
+            log::warn!(
+                "Refused to substitute {}, without the {} prefix",
+                &caps[0],
+                PREFIX
+            );
+            return caps[0].to_string();
+        }
+        match env::var(&caps[1]) {
+            Ok(value) => value,
+            Err(err) => {
+                log::warn!("Failed to substitute {}: {}", &caps[0], err);
+                caps[0].to_string()
+            }
+        }
+    })
+}
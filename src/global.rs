@@ -0,0 +1,30 @@
+use std::sync::{Arc, RwLock};
+
+/// A global set each time the server starts, which unlike a `OnceCell` can be set
+/// again, so that the server can be restarted within the same process.
+///
+/// The requests and tasks which may still be running after the value is replaced keep
+/// the old one alive through their `Arc`, and it is freed once they are done.
+pub(crate) struct Global<T> {
+    value: RwLock<Option<Arc<T>>>,
+}
+
+impl<T> Global<T> {
+    pub(crate) const fn new() -> Self {
+        Global {
+            value: RwLock::new(None),
+        }
+    }
+
+    pub(crate) fn get(&self) -> Option<Arc<T>> {
+        self.value.read().unwrap().clone()
+    }
+
+    pub(crate) fn set(&self, value: T) {
+        *self.value.write().unwrap() = Some(Arc::new(value));
+    }
+
+    pub(crate) fn clear(&self) {
+        *self.value.write().unwrap() = None;
+    }
+}
@@ -0,0 +1,89 @@
+//! The checksums of the served files at `/_live-server/hash`, for the scripts checking the
+//! artifacts they download, e.g. `curl localhost:8080/_live-server/hash?path=dist/app.js`.
+
+use std::path::Path;
+
+use axum::{
+    body::Body,
+    extract::Query,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+};
+use md5::Md5;
+use serde::Deserialize;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use tokio::io::AsyncReadExt;
+
+use crate::server::query_path;
+
+/// The size of the chunks the file is read in, so that large files are not held in memory.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct HashQuery {
+    /// The URL path of the file, e.g. `dist/app.js`.
+    path: String,
+    /// `sha256` by default, `sha512`, `sha1` or `md5`.
+    #[serde(default)]
+    algo: Option<String>,
+}
+
+/// Answer the checksum of the file as a line of `sha256sum` and the like, which they can
+/// check with `-c` in the directory of the file.
+pub(crate) async fn hash(Query(query): Query<HashQuery>) -> (StatusCode, HeaderMap, Body) {
+    let algo = query.algo.as_deref().unwrap_or("sha256");
+    if !matches!(algo, "sha256" | "sha512" | "sha1" | "md5") {
+        let body = Body::from("The supported algorithms are sha256, sha512, sha1 and md5.");
+        return (StatusCode::BAD_REQUEST, HeaderMap::new(), body);
+    }
+    let Some(path) = query_path(&query.path).filter(|path| path.is_file()) else {
+        return (StatusCode::NOT_FOUND, HeaderMap::new(), Body::empty());
+    };
+
+    let result = match algo {
+        "sha512" => digest::<Sha512>(&path).await,
+        "sha1" => digest::<Sha1>(&path).await,
+        "md5" => digest::<Md5>(&path).await,
+        _ => digest::<Sha256>(&path).await,
+    };
+    let hex = match result {
+        Ok(hex) => hex,
+        Err(err) => {
+            log::error!("Failed to hash {:?}: {}", path, err);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                HeaderMap::new(),
+                Body::empty(),
+            );
+        }
+    };
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let mut headers = HeaderMap::new();
+    headers.append(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    (
+        StatusCode::OK,
+        headers,
+        Body::from(format!("{hex}  {name}\n")),
+    )
+}
+
+async fn digest<D: Digest>(path: &Path) -> std::io::Result<String> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = D::new();
+    let mut buf = vec![0; CHUNK_SIZE];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
@@ -0,0 +1,149 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use axum::Json;
+use serde::Serialize;
+
+use crate::stats::serialize_secs;
+
+static WATCHER: Mutex<WatcherState> = Mutex::new(WatcherState::new());
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Health {
+    /// `ok`, or `degraded` if something does not work as expected.
+    status: &'static str,
+    watcher: WatcherStatus,
+}
+
+struct WatcherState {
+    enabled: bool,
+    backend: Option<&'static str>,
+    error: Option<String>,
+    events: u64,
+    ignored: u64,
+    last_change: Option<(String, &'static str, Instant)>,
+    last_error: Option<String>,
+}
+
+impl WatcherState {
+    const fn new() -> Self {
+        WatcherState {
+            enabled: false,
+            backend: None,
+            error: None,
+            events: 0,
+            ignored: 0,
+            last_change: None,
+            last_error: None,
+        }
+    }
+}
+
+/// A snapshot of what the file watcher has been doing, to find out why a page did not
+/// reload, e.g. because the change was ignored or the watcher failed.
+#[derive(Debug, Clone, Serialize)]
+pub struct WatcherStatus {
+    /// Whether the files are watched at all.
+    pub enabled: bool,
+    /// `native` or `poll`.
+    pub backend: Option<&'static str>,
+    /// The reason why the native backend is not in use.
+    pub error: Option<String>,
+    /// Whether the watcher works as expected, without falling back to polling or an error
+    /// since the last events.
+    pub healthy: bool,
+    /// Number of file system events received.
+    pub events: u64,
+    /// Number of those events ignored, e.g. by `--ignore` or the `.gitignore` files.
+    pub ignored: u64,
+    /// The last change which was not ignored.
+    pub last_change: Option<LastChange>,
+    /// The error of the watcher since the last events received without one.
+    pub last_error: Option<String>,
+}
+
+/// A change seen by the file watcher, as in [`WatcherStatus::last_change`].
+#[derive(Debug, Clone, Serialize)]
+pub struct LastChange {
+    /// The URL path if the file is under the root, or the file system path otherwise.
+    pub path: String,
+    /// `create`, `modify`, `rename` or `remove`.
+    pub kind: &'static str,
+    /// Time elapsed since the change.
+    #[serde(serialize_with = "serialize_secs")]
+    pub ago: Duration,
+}
+
+/// Forget the watcher of the previous run, when the server starts again.
+pub(crate) fn reset() {
+    *WATCHER.lock().unwrap() = WatcherState::new();
+}
+
+#[cfg(feature = "watch")]
+pub(crate) fn set_watcher_backend(backend: &'static str, error: Option<String>) {
+    let mut watcher = WATCHER.lock().unwrap();
+    watcher.enabled = true;
+    watcher.backend = Some(backend);
+    watcher.error = error;
+}
+
+/// Count a batch of events received without an error, of which some were ignored.
+#[cfg(feature = "watch")]
+pub(crate) fn record_events(events: usize, ignored: usize) {
+    let mut watcher = WATCHER.lock().unwrap();
+    watcher.events += events as u64;
+    watcher.ignored += ignored as u64;
+    watcher.last_error = None;
+}
+
+#[cfg(feature = "watch")]
+pub(crate) fn record_change(path: &str, kind: &'static str) {
+    WATCHER.lock().unwrap().last_change = Some((path.to_string(), kind, Instant::now()));
+}
+
+#[cfg(feature = "watch")]
+pub(crate) fn record_error(error: String) {
+    WATCHER.lock().unwrap().last_error = Some(error);
+}
+
+/// Return what the file watcher has been doing since the server started, which is also
+/// part of `/_live-server/health` and [`stats`](crate::stats).
+///
+/// ```
+/// let status = live_server::watcher_status();
+/// if let Some(change) = status.last_change {
+///     println!("{} {} {:?} ago", change.kind, change.path, change.ago);
+/// }
+/// ```
+pub fn watcher_status() -> WatcherStatus {
+    let watcher = WATCHER.lock().unwrap();
+    WatcherStatus {
+        enabled: watcher.enabled,
+        backend: watcher.backend,
+        error: watcher.error.clone(),
+        healthy: watcher.error.is_none() && watcher.last_error.is_none(),
+        events: watcher.events,
+        ignored: watcher.ignored,
+        last_change: watcher
+            .last_change
+            .as_ref()
+            .map(|(path, kind, time)| LastChange {
+                path: path.clone(),
+                kind,
+                ago: time.elapsed(),
+            }),
+        last_error: watcher.last_error.clone(),
+    }
+}
+
+pub(crate) async fn health() -> Json<Health> {
+    let watcher = watcher_status();
+    let status = match watcher.healthy {
+        true => "ok",
+        false => "degraded",
+    };
+
+    Json(Health { status, watcher })
+}
@@ -0,0 +1,152 @@
+//! Serving the same router over HTTP/3 on the UDP port of the TCP listener, with a
+//! self-signed certificate, as enabled by [`Listener::http3`](crate::Listener::http3).
+
+use std::{error::Error, net::SocketAddr, sync::Arc};
+
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request},
+    http::{header, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::Response,
+    Router,
+};
+use bytes::{Buf, Bytes};
+use futures::StreamExt;
+use h3::server::RequestResolver;
+use quinn::crypto::rustls::QuicServerConfig;
+use rustls::pki_types::PrivatePkcs8KeyDer;
+use tower::{Layer, ServiceExt};
+
+use crate::global::Global;
+use crate::server::{shutdown, strip_base};
+use crate::OPTIONS;
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// The largest request body collected, the same as the default of axum over TCP, unless
+/// the uploads allow a larger one.
+const MAX_BODY: u64 = 2 * 1024 * 1024;
+
+/// The UDP port advertised in `Alt-Svc`, set while serving over HTTP/3.
+pub(crate) static PORT: Global<u16> = Global::new();
+
+/// Bind the UDP socket at the address, with a certificate for `localhost` and the host.
+pub(crate) fn bind(addr: SocketAddr) -> Result<quinn::Endpoint, BoxError> {
+    let names = vec!["localhost".to_string(), addr.ip().to_string()];
+    let certified = rcgen::generate_simple_self_signed(names)?;
+    let key = PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der());
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut tls = rustls::ServerConfig::builder_with_provider(provider)
+        .with_protocol_versions(&[&rustls::version::TLS13])?
+        .with_no_client_auth()
+        .with_single_cert(vec![certified.cert.der().clone()], key.into())?;
+    tls.alpn_protocols = vec![b"h3".to_vec()];
+
+    let config = quinn::ServerConfig::with_crypto(Arc::new(QuicServerConfig::try_from(tls)?));
+    let endpoint = quinn::Endpoint::server(config, addr)?;
+    let port = endpoint.local_addr()?.port();
+    PORT.set(port);
+    log::info!(
+        "Serving HTTP/3 on UDP port {port} with a self-signed certificate, \
+        e.g. for `curl --http3-only -k https://localhost:{port}/`"
+    );
+    Ok(endpoint)
+}
+
+pub(crate) async fn serve(endpoint: quinn::Endpoint, router: Router) {
+    loop {
+        let incoming = tokio::select! {
+            incoming = endpoint.accept() => incoming,
+            _ = shutdown().cancelled_owned() => None,
+        };
+        let Some(incoming) = incoming else { break };
+        let router = router.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle_connection(incoming, router).await {
+                log::debug!("HTTP/3 connection ended: {}", err);
+            }
+        });
+    }
+    endpoint.close(0u32.into(), b"shutting down");
+    PORT.clear();
+}
+
+async fn handle_connection(incoming: quinn::Incoming, router: Router) -> Result<(), BoxError> {
+    let connection = incoming.await?;
+    let remote = connection.remote_address();
+    let mut connection = h3::server::Connection::new(h3_quinn::Connection::new(connection)).await?;
+
+    loop {
+        let resolver = tokio::select! {
+            resolver = connection.accept() => resolver?,
+            _ = shutdown().cancelled_owned() => {
+                connection.shutdown(0).await?;
+                break;
+            }
+        };
+        let Some(resolver) = resolver else { break };
+        let router = router.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle_request(resolver, router, remote).await {
+                log::debug!("HTTP/3 request failed: {}", err);
+            }
+        });
+    }
+    Ok(())
+}
+
+async fn handle_request(
+    resolver: RequestResolver<h3_quinn::Connection, Bytes>,
+    router: Router,
+    remote: SocketAddr,
+) -> Result<(), BoxError> {
+    let (req, mut stream) = resolver.resolve_request().await?;
+    let limit = OPTIONS
+        .get()
+        .and_then(|options| options.upload_limit)
+        .map_or(MAX_BODY, |limit| limit.max(MAX_BODY));
+    let mut body = Vec::new();
+    while let Some(mut chunk) = stream.recv_data().await? {
+        if (body.len() + chunk.remaining()) as u64 > limit {
+            log::warn!("Refused an HTTP/3 request body larger than {limit} bytes");
+            let response = Response::builder()
+                .status(StatusCode::PAYLOAD_TOO_LARGE)
+                .body(())?;
+            stream.send_response(response).await?;
+            stream.finish().await?;
+            return Ok(());
+        }
+        body.extend_from_slice(&chunk.copy_to_bytes(chunk.remaining()));
+    }
+    let mut req = req.map(|()| Body::from(body));
+    req.extensions_mut().insert(ConnectInfo(remote));
+
+    // The same stack as the TCP listener, see `server::serve`.
+    let app = middleware::from_fn(strip_base).layer(router);
+    let response = app.oneshot(req).await?;
+
+    let (parts, body) = response.into_parts();
+    stream
+        .send_response(Response::from_parts(parts, ()))
+        .await?;
+    let mut data = body.into_data_stream();
+    while let Some(chunk) = data.next().await {
+        stream.send_data(chunk?).await?;
+    }
+    stream.finish().await?;
+    Ok(())
+}
+
+/// Tell the browsers that the site is also available over HTTP/3.
+pub(crate) async fn advertise(req: Request, next: Next) -> Response {
+    let mut response = next.run(req).await;
+    if let Some(port) = PORT.get() {
+        let value = format!("h3=\":{port}\"; ma=86400");
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            response.headers_mut().insert(header::ALT_SVC, value);
+        }
+    }
+    response
+}
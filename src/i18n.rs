@@ -0,0 +1,242 @@
+use axum::http::{header, HeaderMap};
+
+/// The user-facing text of the directory listing and the error page.
+#[derive(Debug)]
+#[cfg_attr(not(feature = "listing"), allow(dead_code))]
+pub(crate) struct Strings {
+    /// The language tag, e.g. `en`, used for `<html lang>`.
+    pub(crate) lang: &'static str,
+    pub(crate) name: &'static str,
+    pub(crate) size: &'static str,
+    pub(crate) modified: &'static str,
+    pub(crate) filter: &'static str,
+    pub(crate) download_zip: &'static str,
+    pub(crate) previous: &'static str,
+    pub(crate) next: &'static str,
+    /// `{page}` and `{pages}` are replaced with the numbers.
+    pub(crate) page_of: &'static str,
+    pub(crate) upload: &'static str,
+    pub(crate) rename: &'static str,
+    pub(crate) delete: &'static str,
+    /// `{name}` is replaced with the name of the file, in the browser.
+    pub(crate) delete_confirm: &'static str,
+    pub(crate) admin_token: &'static str,
+    pub(crate) toggle_theme: &'static str,
+    pub(crate) close: &'static str,
+    pub(crate) truncated: &'static str,
+    pub(crate) not_found: &'static str,
+    /// Shown while the root does not exist yet, `{dir}` is replaced with its path.
+    pub(crate) waiting: &'static str,
+}
+
+impl Strings {
+    #[cfg(feature = "listing")]
+    pub(crate) fn page_of(&self, page: usize, pages: usize) -> String {
+        self.page_of
+            .replace("{page}", &page.to_string())
+            .replace("{pages}", &pages.to_string())
+    }
+
+    pub(crate) fn waiting(&self, dir: &str) -> String {
+        self.waiting.replace("{dir}", dir)
+    }
+}
+
+const EN: Strings = Strings {
+    lang: "en",
+    name: "Name",
+    size: "Size",
+    modified: "Modified",
+    filter: "Filter",
+    download_zip: "Download as zip",
+    previous: "Previous",
+    next: "Next",
+    page_of: "Page {page} of {pages}",
+    upload: "Upload",
+    rename: "Rename",
+    delete: "Delete",
+    delete_confirm: "Delete {name}?",
+    admin_token: "Admin token",
+    toggle_theme: "Toggle dark mode",
+    close: "Close",
+    truncated: "The file is truncated.",
+    not_found: "File not found",
+    waiting: "Waiting for {dir} to be created…",
+};
+
+const ZH: Strings = Strings {
+    lang: "zh",
+    name: "名称",
+    size: "大小",
+    modified: "修改时间",
+    filter: "筛选",
+    download_zip: "下载为 zip",
+    previous: "上一页",
+    next: "下一页",
+    page_of: "第 {page} 页，共 {pages} 页",
+    upload: "上传",
+    rename: "重命名",
+    delete: "删除",
+    delete_confirm: "删除 {name}？",
+    admin_token: "管理令牌",
+    toggle_theme: "切换深色模式",
+    close: "关闭",
+    truncated: "文件已截断。",
+    not_found: "文件不存在",
+    waiting: "正在等待创建 {dir}…",
+};
+
+const JA: Strings = Strings {
+    lang: "ja",
+    name: "名前",
+    size: "サイズ",
+    modified: "更新日時",
+    filter: "絞り込み",
+    download_zip: "zip でダウンロード",
+    previous: "前へ",
+    next: "次へ",
+    page_of: "{page} / {pages} ページ",
+    upload: "アップロード",
+    rename: "名前を変更",
+    delete: "削除",
+    delete_confirm: "{name} を削除しますか？",
+    admin_token: "管理トークン",
+    toggle_theme: "ダークモードの切り替え",
+    close: "閉じる",
+    truncated: "ファイルは途中で切り詰められています。",
+    not_found: "ファイルが見つかりません",
+    waiting: "{dir} が作成されるのを待っています…",
+};
+
+const DE: Strings = Strings {
+    lang: "de",
+    name: "Name",
+    size: "Größe",
+    modified: "Geändert",
+    filter: "Filtern",
+    download_zip: "Als zip herunterladen",
+    previous: "Zurück",
+    next: "Weiter",
+    page_of: "Seite {page} von {pages}",
+    upload: "Hochladen",
+    rename: "Umbenennen",
+    delete: "Löschen",
+    delete_confirm: "{name} löschen?",
+    admin_token: "Admin-Token",
+    toggle_theme: "Dunkelmodus umschalten",
+    close: "Schließen",
+    truncated: "Die Datei ist gekürzt.",
+    not_found: "Datei nicht gefunden",
+    waiting: "Warte darauf, dass {dir} erstellt wird…",
+};
+
+const FR: Strings = Strings {
+    lang: "fr",
+    name: "Nom",
+    size: "Taille",
+    modified: "Modifié",
+    filter: "Filtrer",
+    download_zip: "Télécharger en zip",
+    previous: "Précédent",
+    next: "Suivant",
+    page_of: "Page {page} sur {pages}",
+    upload: "Téléverser",
+    rename: "Renommer",
+    delete: "Supprimer",
+    delete_confirm: "Supprimer {name} ?",
+    admin_token: "Jeton d’administration",
+    toggle_theme: "Basculer le mode sombre",
+    close: "Fermer",
+    truncated: "Le fichier est tronqué.",
+    not_found: "Fichier introuvable",
+    waiting: "En attente de la création de {dir}…",
+};
+
+const ES: Strings = Strings {
+    lang: "es",
+    name: "Nombre",
+    size: "Tamaño",
+    modified: "Modificado",
+    filter: "Filtrar",
+    download_zip: "Descargar como zip",
+    previous: "Anterior",
+    next: "Siguiente",
+    page_of: "Página {page} de {pages}",
+    upload: "Subir",
+    rename: "Renombrar",
+    delete: "Eliminar",
+    delete_confirm: "¿Eliminar {name}?",
+    admin_token: "Token de administración",
+    toggle_theme: "Cambiar modo oscuro",
+    close: "Cerrar",
+    truncated: "El archivo está truncado.",
+    not_found: "Archivo no encontrado",
+    waiting: "Esperando a que se cree {dir}…",
+};
+
+const LANGUAGES: [&Strings; 6] = [&EN, &ZH, &JA, &DE, &FR, &ES];
+
+/// Find the strings of the language, e.g. `de` or `zh-CN`, by its primary subtag.
+pub(crate) fn find(lang: &str) -> Option<&'static Strings> {
+    let primary = lang.split(['-', '_']).next()?.trim().to_lowercase();
+    LANGUAGES
+        .into_iter()
+        .find(|strings| strings.lang == primary)
+}
+
+/// Choose the language configured by [`Listener::lang`](crate::Listener::lang), or the
+/// preferred one from the `Accept-Language` header, falling back to English.
+pub(crate) fn negotiate(configured: Option<&str>, headers: &HeaderMap) -> &'static Strings {
+    if let Some(strings) = configured.and_then(find) {
+        return strings;
+    }
+
+    let Some(accept) = headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return &EN;
+    };
+
+    let mut languages: Vec<(&str, f32)> = accept
+        .split(',')
+        .map(|item| {
+            let mut parts = item.split(';');
+            let lang = parts.next().unwrap_or_default().trim();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .and_then(|q| q.parse().ok())
+                .unwrap_or(1.0);
+            (lang, quality)
+        })
+        .collect();
+    languages.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    languages
+        .into_iter()
+        .find_map(|(lang, _)| find(lang))
+        .unwrap_or(&EN)
+}
+
+/// Fill in the `{{ t_* }}` placeholders of the template.
+pub(crate) fn translate(template: String, strings: &Strings) -> String {
+    [
+        ("lang", strings.lang),
+        ("t_name", strings.name),
+        ("t_size", strings.size),
+        ("t_modified", strings.modified),
+        ("t_filter", strings.filter),
+        ("t_download_zip", strings.download_zip),
+        ("t_upload", strings.upload),
+        ("t_rename", strings.rename),
+        ("t_delete", strings.delete),
+        ("t_delete_confirm", strings.delete_confirm),
+        ("t_admin_token", strings.admin_token),
+        ("t_toggle_theme", strings.toggle_theme),
+        ("t_close", strings.close),
+    ]
+    .into_iter()
+    .fold(template, |template, (name, value)| {
+        crate::util::render(template, name, value)
+    })
+}
@@ -14,64 +14,1680 @@
 //! env_logger::init();
 //! ```
 
+mod a11y;
+mod admin;
+mod archive;
+mod client_log;
+#[cfg(feature = "listing")]
+mod download;
+mod env_vars;
+mod global;
+mod hash;
+mod health;
+#[cfg(feature = "http3")]
+mod http3;
+mod i18n;
+#[cfg(feature = "listing")]
 mod listing;
+mod manage;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod mock;
+#[cfg(feature = "otel")]
+mod otel;
+mod preload;
+#[cfg(feature = "listing")]
+mod preview;
+mod protocol;
 mod server;
+mod ssi;
 mod static_files;
+mod stats;
+mod storage;
+pub mod test;
+mod upload;
+mod util;
+mod virtual_files;
 mod watcher;
+#[cfg(feature = "webdav")]
+mod webdav;
 
 use std::{
     error::Error,
-    net::IpAddr,
+    fmt,
+    future::Future,
+    io::ErrorKind,
+    net::{IpAddr, SocketAddr},
     path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
 };
 
-use axum::Router;
+use axum::{http::HeaderValue, Router};
+use global::Global;
+use globset::Glob;
 use local_ip_address::local_ip;
 use path_slash::PathExt;
-use server::{create_listener, create_server};
-use tokio::{
-    net::TcpListener,
-    sync::{broadcast, OnceCell},
-};
-use watcher::{create_watcher, Watcher};
+use regex::Regex;
+use server::{create_listener, create_server, HeaderHook, HtmlTransform, ServerOptions, Snippet};
+use tokio::{net::TcpListener, sync::broadcast};
+use tokio_util::sync::{CancellationToken, DropGuard};
+use watcher::WatchOptions;
+#[cfg(feature = "watch")]
+use watcher::{create_watcher, Filter};
+
+pub use axum::http::HeaderMap;
+pub use health::{watcher_status, LastChange, WatcherStatus};
+pub use stats::{stats, ClientStats, RequestSummary, Stats};
+pub use storage::{DiskStorage, MemoryStorage, Storage};
+pub use virtual_files::{remove_virtual_file, set_virtual_file};
+pub use watcher::{reload, Change};
+
+static WATCH: Global<bool> = Global::new();
+static ROOT: Global<PathBuf> = Global::new();
+static TX: Global<broadcast::Sender<String>> = Global::new();
+static OPTIONS: Global<ServerOptions> = Global::new();
+static STORAGE: Global<Box<dyn Storage>> = Global::new();
+
+/// The reason why live-server failed to start.
+#[derive(Debug)]
+pub enum ListenError {
+    /// Failed to bind the listener to the address.
+    Bind(String),
+    /// Failed to resolve the root path.
+    Root(String),
+    /// Failed to set up the file watcher.
+    Watcher(String),
+    /// The last run of the command set by [`Listener::exec`] failed, as returned once the
+    /// server stops. The files are still watched and served meanwhile, with the failure
+    /// shown on the pages.
+    Exec(String),
+    /// An option set on the listener is invalid, e.g. a rewrite pattern which is not a
+    /// regex.
+    Options(String),
+}
+
+impl fmt::Display for ListenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenError::Bind(msg)
+            | ListenError::Root(msg)
+            | ListenError::Watcher(msg)
+            | ListenError::Exec(msg)
+            | ListenError::Options(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl Error for ListenError {}
+
+pub struct Listener {
+    tcp_listener: TcpListener,
+    router: Router,
+    root_path: PathBuf,
+    watch: bool,
+    watch_options: WatchOptions,
+    server_options: ServerOptions,
+    storage: Box<dyn Storage>,
+    admin_token: Option<String>,
+    shutdown_signal: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+    /// The files of [`listen_bundle`], served once the server starts.
+    bundle: Option<Vec<(String, Vec<u8>)>>,
+    /// The files of [`Listener::virtual_file`] and [`Listener::virtual_route`], served
+    /// once the server starts rather than by the server running meanwhile.
+    virtual_files: Vec<(String, virtual_files::Content)>,
+    /// The options as set, checked when the server starts rather than by the builders.
+    unchecked: Unchecked,
+}
+
+/// The options of the builders which may be invalid, see [`Listener::check_options`].
+#[derive(Default)]
+struct Unchecked {
+    server_header: Option<String>,
+    delays: Vec<(Option<String>, Duration)>,
+    rewrites: Vec<(String, String)>,
+}
+
+/// Ends whatever is left of a run when it stops, so that the server can start again.
+struct Stop {
+    _cancel: DropGuard,
+}
+
+impl Drop for Stop {
+    fn drop(&mut self) {
+        virtual_files::clear();
+        #[cfg(feature = "mmap")]
+        mmap::clear();
+    }
+}
+
+impl Listener {
+    /// Start live-server.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true).await?.start().await
+    /// }
+    /// ```
+    ///
+    /// Once it has stopped, e.g. by [`Listener::graceful_shutdown`], it can be started
+    /// again with [`listen`], with the same or other options:
+    ///
+    /// ```
+    /// use live_server::listen;
+    /// use std::time::Duration;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     // Serve for a minute, then serve another directory without watching.
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .graceful_shutdown(tokio::time::sleep(Duration::from_secs(60)))
+    ///         .start()
+    ///         .await?;
+    ///     listen("127.0.0.1:8080", "./public", false).await?.start().await
+    /// }
+    /// ```
+    pub async fn start(mut self) -> Result<(), Box<dyn Error>> {
+        self.check_options().map_err(|err| {
+            log::error!("{}", err);
+            err
+        })?;
+        for page in self.server_options.error_pages.values_mut() {
+            *page = self.root_path.join(&page);
+        }
+        let mounts = self.server_options.mounts.iter_mut();
+        for (_, dir) in mounts.chain(self.server_options.mocks.iter_mut()) {
+            match canonicalize(&dir).await {
+                Ok(path) => *dir = path,
+                Err(err) => {
+                    let err_msg = format!(
+                        "Failed to get absolute path of {:?}: {}",
+                        path_to_string_but_readable(dir),
+                        err
+                    );
+                    log::error!("{}", err_msg);
+                    return Err(ListenError::Root(err_msg).into());
+                }
+            }
+            self.watch_options.extra_paths.push(dir.clone());
+        }
+
+        #[cfg(feature = "http3")]
+        let http3 = match self.server_options.http3 {
+            true => {
+                let addr = self.tcp_listener.local_addr()?;
+                let endpoint = http3::bind(addr).map_err(|err| {
+                    let err_msg = format!("Failed to serve HTTP/3 on {}: {}", addr, err);
+                    log::error!("{}", err_msg);
+                    ListenError::Bind(err_msg)
+                })?;
+                Some(endpoint)
+            }
+            false => None,
+        };
+
+        #[cfg(feature = "otel")]
+        let _otel = match self.server_options.otel {
+            true => Some(otel::init().map_err(|err| {
+                log::error!("Failed to export the traces: {}", err);
+                err
+            })?),
+            false => None,
+        };
+
+        if self.server_options.pristine && self.watch {
+            log::info!("Not watching the files, since they are served as they are");
+            self.watch = false;
+        }
+        // The bundled files are reloaded by `set_virtual_file` rather than the watcher.
+        let reload = self.watch || self.bundle.is_some() && !self.server_options.pristine;
+        WATCH.set(reload);
+        for (path, content) in self.bundle.take().unwrap_or_default() {
+            virtual_files::insert_bytes(&path, content);
+        }
+        for (path, content) in std::mem::take(&mut self.virtual_files) {
+            virtual_files::insert(&path, content);
+        }
+
+        ROOT.set(self.root_path.clone());
+        let (tx, _) = broadcast::channel(self.server_options.channel_capacity);
+        TX.set(tx);
+        OPTIONS.set(self.server_options);
+        STORAGE.set(self.storage);
+
+        stats::STATS.start();
+        health::reset();
+        match self.admin_token {
+            Some(token) => admin::ADMIN_TOKEN.set(token),
+            None => admin::ADMIN_TOKEN.clear(),
+        }
+        let shutdown = CancellationToken::new();
+        server::SHUTDOWN.set(shutdown.clone());
+        if let Some(signal) = self.shutdown_signal.take() {
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move {
+                signal.await;
+                log::info!("Shutting down");
+                shutdown.cancel();
+            });
+        }
+        let _stop = Stop {
+            _cancel: shutdown.drop_guard(),
+        };
+
+        #[cfg(feature = "http3")]
+        if let Some(endpoint) = http3 {
+            tokio::spawn(http3::serve(endpoint, self.router.clone()));
+        }
+        #[cfg_attr(not(feature = "watch"), allow(unused_mut))]
+        let mut server_future = tokio::spawn(server::serve(self.tcp_listener, self.router));
+
+        #[cfg(not(feature = "watch"))]
+        if self.watch {
+            log::warn!("Built without the `watch` feature, so the files are not watched");
+        }
+        #[cfg(feature = "watch")]
+        if self.watch {
+            let mut extra_paths = vec![];
+            for path in &self.watch_options.extra_paths {
+                match canonicalize(path).await {
+                    Ok(path) => extra_paths.push(path),
+                    Err(err) => {
+                        let err_msg = format!(
+                            "Failed to get absolute path of {:?}: {}",
+                            path_to_string_but_readable(path),
+                            err
+                        );
+                        log::error!("{}", err_msg);
+                        return Err(ListenError::Watcher(err_msg).into());
+                    }
+                }
+            }
+            self.watch_options.extra_paths = extra_paths;
+            if let Some(marker) = &self.watch_options.reload_marker {
+                self.watch_options.reload_marker = Some(self.root_path.join(marker));
+            }
+
+            let filter = Filter::new(&self.root_path, &self.watch_options)
+                .await
+                .map_err(ListenError::Watcher)?;
+            let watcher = create_watcher(&self.watch_options)
+                .await
+                .map_err(ListenError::Watcher)?;
+            // A failure left over from a server which stopped without returning it.
+            watcher::take_exec_failure();
+            let mut watcher_future = tokio::spawn(watcher::watch(self.root_path, watcher, filter));
+            tokio::select! {
+                result = &mut watcher_future => {
+                    server_future.abort();
+                    result??;
+                }
+                result = &mut server_future => {
+                    watcher_future.abort();
+                    result?;
+                }
+            }
+            if let Some(err_msg) = watcher::take_exec_failure() {
+                return Err(ListenError::Exec(err_msg).into());
+            }
+            return Ok(());
+        }
+
+        server_future.await?;
+        Ok(())
+    }
+
+    /// Check the options set by the builders which may be invalid, so that [`Listener::start`]
+    /// returns an error rather than the builders panicking.
+    fn check_options(&mut self) -> Result<(), ListenError> {
+        let unchecked = std::mem::take(&mut self.unchecked);
+        let options = &mut self.server_options;
+        if options.channel_capacity == 0 {
+            let err_msg = "The channel capacity must be at least 1".to_string();
+            return Err(ListenError::Options(err_msg));
+        }
+        if let Some(value) = unchecked.server_header {
+            let header = HeaderValue::from_str(&value).map_err(|err| {
+                ListenError::Options(format!("Invalid Server header {:?}: {}", value, err))
+            })?;
+            options.server_header = Some(header);
+        }
+        for (pattern, duration) in unchecked.delays {
+            let glob = match pattern {
+                Some(pattern) => Some(Glob::new(&pattern).map_err(|err| {
+                    ListenError::Options(format!("Invalid delay pattern {:?}: {}", pattern, err))
+                })?),
+                None => None,
+            };
+            let matcher = glob.map(|glob| glob.compile_matcher());
+            options.delays.push((matcher, duration));
+        }
+        for (pattern, replacement) in unchecked.rewrites {
+            let regex = Regex::new(&format!("^(?:{})$", pattern)).map_err(|err| {
+                ListenError::Options(format!("Invalid rewrite pattern {:?}: {}", pattern, err))
+            })?;
+            options.rewrites.push((regex, replacement));
+        }
+        Ok(())
+    }
+
+    /// Shut down gracefully when the future completes, e.g. on Ctrl-C: stop accepting
+    /// connections, close the WebSockets, finish the responses in flight, stop watching,
+    /// and then return from [`Listener::start`].
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .graceful_shutdown(async {
+    ///             tokio::signal::ctrl_c().await.ok();
+    ///         })
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn graceful_shutdown<F: Future<Output = ()> + Send + 'static>(mut self, signal: F) -> Self {
+        self.shutdown_signal = Some(Box::pin(signal));
+        self
+    }
+
+    /// Ignore file changes matching the glob pattern, so that they will not trigger a reload.
+    ///
+    /// The pattern is matched against the path relative to the root, e.g. `node_modules/**`
+    /// or `*.swp`. It can be called multiple times to add more patterns.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .ignore("node_modules/**")
+    ///         .ignore("*.swp")
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn ignore<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.watch_options.ignore.push(pattern.into());
+        self
+    }
+
+    /// Do not trigger a reload for file changes ignored by `.gitignore` or `.ignore` files
+    /// under the root, as well as changes inside the `.git` directory.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .gitignore(true)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn gitignore(mut self, enabled: bool) -> Self {
+        self.watch_options.gitignore = enabled;
+        self
+    }
+
+    /// Only trigger a reload when files with the extension change, e.g. `html` or `css`.
+    /// It can be called multiple times to add more extensions. All files are watched by default.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .watch_extension("html")
+    ///         .watch_extension("css")
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn watch_extension<S: Into<String>>(mut self, extension: S) -> Self {
+        self.watch_options.extensions.push(extension.into());
+        self
+    }
+
+    /// Set how long to wait for file changes to settle before triggering a reload.
+    /// Defaults to 200ms.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .debounce(Duration::from_millis(800))
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn debounce(mut self, duration: Duration) -> Self {
+        self.watch_options.debounce = duration;
+        self
+    }
+
+    /// When the root is a single file, also reload when the other files in its directory
+    /// change, e.g. the images it shows. Only the file itself is watched by default.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./presentation.html", true)
+    ///         .await?
+    ///         .watch_siblings(true)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn watch_siblings(mut self, enabled: bool) -> Self {
+        self.watch_options.watch_siblings = enabled;
+        self
+    }
+
+    /// Only watch the directories up to `depth` levels below the root, where `0` means only
+    /// the files directly inside the root are watched. The whole tree is watched by default.
+    ///
+    /// This is useful when deep vendored trees exhaust the inotify watches.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .watch_depth(2)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn watch_depth(mut self, depth: usize) -> Self {
+        self.watch_options.max_depth = Some(depth);
+        self
+    }
+
+    /// Also watch a path outside of the root, e.g. the source directory whose build output
+    /// is served, so that editing the sources triggers a reload as well.
+    /// It can be called multiple times to add more paths.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./dist", true)
+    ///         .await?
+    ///         .watch_path("./src")
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn watch_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.watch_options.extra_paths.push(path.into());
+        self
+    }
+
+    /// Run the shell command when files change, and only reload after it exits successfully,
+    /// e.g. `npm run build`. The changes made while the command runs, such as its output,
+    /// do not trigger another run. If its last run failed, [`Listener::start`] returns
+    /// [`ListenError::Exec`] once the server stops, for the scripts to tell it apart.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./dist", true)
+    ///         .await?
+    ///         .watch_path("./src")
+    ///         .exec("npm run build")
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn exec<S: Into<String>>(mut self, command: S) -> Self {
+        self.watch_options.exec = Some(command.into());
+        self
+    }
+
+    /// Hold the reload until no file has changed for the duration, so that the browser
+    /// does not reload halfway through a generator writing many files.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .quiet_period(Duration::from_secs(1))
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn quiet_period(mut self, duration: Duration) -> Self {
+        self.watch_options.quiet_period = Some(duration);
+        self
+    }
+
+    /// Only reload when the marker file is touched, e.g. by the last step of a build.
+    /// A relative path is resolved against the root.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .reload_marker(".build-done")
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn reload_marker<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.watch_options.reload_marker = Some(path.into());
+        self
+    }
+
+    /// Insert `<link rel="modulepreload">` and `<link rel="preload">` hints into the served HTML
+    /// for the nested imports of its module scripts and stylesheets, so that pages with deep
+    /// dependency chains load faster after a reload.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .preload_hints(true)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn preload_hints(mut self, enabled: bool) -> Self {
+        self.server_options.preload_hints = enabled;
+        self
+    }
+
+    /// Inject a script which runs [axe-core](https://github.com/dequelabs/axe-core) after each
+    /// page load and posts the violations to `/_live-server/api/a11y`, where they are logged.
+    /// The latest report of each page can be fetched from the same endpoint with `GET`.
+    ///
+    /// axe-core itself is not bundled, so the page needs to load it, e.g. from a CDN.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .a11y(true)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn a11y(mut self, enabled: bool) -> Self {
+        self.server_options.a11y = enabled;
+        self
+    }
+
+    /// Inject a script which posts the uncaught errors and unhandled promise rejections of
+    /// the page to `/_live-server/client-log`, where they are logged. This is useful to see
+    /// the errors of browsers without developer tools, e.g. on a phone or a TV.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .client_log(true)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn client_log(mut self, enabled: bool) -> Self {
+        self.server_options.client_log = enabled;
+        self
+    }
+
+    /// Inject the HTML snippet into the served pages, e.g. a `<script>` loading a debug
+    /// toolbar. It can be called multiple times to add more snippets.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .inject(r#"<script src="https://cdn.jsdelivr.net/npm/eruda"></script>"#)
+    ///         .inject("<script>eruda.init();</script>")
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn inject<S: Into<String>>(mut self, snippet: S) -> Self {
+        self.server_options
+            .snippets
+            .push(Snippet::Inline(snippet.into()));
+        self
+    }
+
+    /// Inject the content of the file into the served pages, like [`Listener::inject`].
+    /// The file is read on every request, so editing it takes effect on the next reload.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .inject_file("./debug.html")
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn inject_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.server_options
+            .snippets
+            .push(Snippet::File(path.into()));
+        self
+    }
+
+    /// Rewrite the served pages before the scripts are injected, e.g. to insert an
+    /// analytics stub or to point the asset URLs at a local build. The callback gets the
+    /// request path and the HTML, and returns the new HTML. Each call adds a transform,
+    /// which runs after those added before.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .transform_html(|_path, html| html.replace("https://cdn.example.com/", "/vendor/"))
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn transform_html<F: Fn(&str, String) -> String + Send + Sync + 'static>(
+        mut self,
+        transform: F,
+    ) -> Self {
+        self.server_options
+            .html_transforms
+            .push(HtmlTransform(Arc::new(transform)));
+        self
+    }
+
+    /// Adjust the headers of each response, e.g. to set a signed cookie or a different
+    /// `Content-Security-Policy` per page. The callback gets the request path and the
+    /// headers. Each call adds a callback, which runs after those added before.
+    ///
+    /// ```
+    /// use live_server::{listen, HeaderMap};
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .response_headers(|path, headers: &mut HeaderMap| {
+    ///             if path.starts_with("/admin/") {
+    ///                 headers.insert("content-security-policy", "default-src 'self'".parse().unwrap());
+    ///             }
+    ///         })
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn response_headers<F: Fn(&str, &mut HeaderMap) + Send + Sync + 'static>(
+        mut self,
+        callback: F,
+    ) -> Self {
+        self.server_options
+            .header_hooks
+            .push(HeaderHook(Arc::new(callback)));
+        self
+    }
+
+    /// Send the `Server` header with the value in every response, e.g. to look like the
+    /// production server. live-server sends no `Server` header, nor any other header
+    /// identifying it, unless set here.
+    ///
+    /// [`Listener::start`] fails if the value is not a valid header value, e.g. if it
+    /// contains a newline.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .server_header("nginx")
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn server_header<S: AsRef<str>>(mut self, value: S) -> Self {
+        self.unchecked.server_header = Some(value.as_ref().to_string());
+        self
+    }
+
+    /// Accept `PUT` requests writing a file at their path, and multipart `POST` requests
+    /// with files to add to the directory at their path, e.g. from the form the directory
+    /// listing then shows, so that others on the network can drop files in. Each file
+    /// may be up to `max_bytes` long.
+    ///
+    /// The files can only be written where they would be served, so not outside of the
+    /// root nor to dotfiles unless [`Listener::show_dotfiles`] is set. A `POST` never
+    /// replaces a file, saving `photo (1).jpg` next to an existing `photo.jpg`.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("0.0.0.0:8080", "./shared", true)
+    ///         .await?
+    ///         .upload(100 * 1024 * 1024)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn upload(mut self, max_bytes: u64) -> Self {
+        self.server_options.upload_limit = Some(max_bytes);
+        self
+    }
+
+    /// Also serve over HTTP/3 on the UDP port of the same number, and advertise it with
+    /// the `Alt-Svc` header. Experimental, and only with the `http3` feature.
+    ///
+    /// QUIC always encrypts, so the server uses a self-signed certificate, which the
+    /// clients have to be told to accept, e.g. `curl --http3-only -k`. The browsers only
+    /// switch to HTTP/3 for HTTPS sites, unless forced to, e.g. Chrome with
+    /// `--origin-to-force-quic-on=localhost:8080 --ignore-certificate-errors`.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .http3(true)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    #[cfg(feature = "http3")]
+    pub fn http3(mut self, enabled: bool) -> Self {
+        self.server_options.http3 = enabled;
+        self
+    }
+
+    /// Also serve the root over WebDAV under `/_live-server/webdav/`, so that it can be
+    /// mounted as a network drive, e.g. with Finder's "Connect to Server" or Windows'
+    /// "Map network drive". Only with the `webdav` feature.
+    ///
+    /// Anyone who can reach the server can then read the files under the root, but not the
+    /// mounted directories or the refused dotfiles. Writing, moving and deleting them also
+    /// takes the token of [`Listener::admin_token`], as the password the WebDAV clients
+    /// ask for or as a bearer token, unless [`Listener::upload`] lets anyone write.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .webdav(true)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    #[cfg(feature = "webdav")]
+    pub fn webdav(mut self, enabled: bool) -> Self {
+        self.server_options.webdav = enabled;
+        self
+    }
+
+    /// Export a span for each request over OTLP/HTTP, e.g. to Jaeger, continuing the trace
+    /// of the `traceparent` header. Only with the `otel` feature.
+    ///
+    /// The exporter is configured by the usual variables, e.g. `OTEL_EXPORTER_OTLP_ENDPOINT`,
+    /// which defaults to `http://localhost:4318`, and `OTEL_SERVICE_NAME`, which defaults to
+    /// `live-server`. The requests of live-server's own script and assets are left out.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .otel(true)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    #[cfg(feature = "otel")]
+    pub fn otel(mut self, enabled: bool) -> Self {
+        self.server_options.otel = enabled;
+        self
+    }
+
+    /// Serve the files of `min_size` bytes or more from memory maps, which are kept across
+    /// the requests until the file changes, rather than reading them again each time, e.g.
+    /// for the large WASM modules and data files reloaded over and over while profiling.
+    /// Only the files sent as they are, not the pages or the scripts with `{{ env:NAME }}`.
+    /// Only with the `mmap` feature.
+    ///
+    /// A file truncated in place, e.g. by a build writing it rather than replacing it,
+    /// makes the whole process crash with `SIGBUS` while it is being sent, or when it is
+    /// next sent from the map kept from before if the change was not noticed. The maps are
+    /// dropped when the watcher sees the file change, and only the last 64 are kept.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .mmap(16 * 1024 * 1024)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    #[cfg(feature = "mmap")]
+    pub fn mmap(mut self, min_size: u64) -> Self {
+        self.server_options.mmap = Some(min_size);
+        self
+    }
+
+    /// Load the injected scripts from `/_live-server/client.js` instead of inlining them, so
+    /// that they are allowed by a Content-Security-Policy with `script-src 'self'`. If the
+    /// page sets a nonce in its `<meta http-equiv="Content-Security-Policy">`, it is added to
+    /// the script as well. The snippets added by [`Listener::inject`] are still inlined.
+    ///
+    /// The policy also needs to allow connecting to the server, e.g. `connect-src 'self' ws:`.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .csp(true)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn csp(mut self, enabled: bool) -> Self {
+        self.server_options.csp = enabled;
+        self
+    }
+
+    /// Serve the files exactly as they are on the disk, like a production server would, e.g.
+    /// for benchmarks or pixel-perfect comparisons: nothing is injected into the pages, the
+    /// environment variables are not substituted, and the files are not watched. The
+    /// directory listings, mounts and response headers are kept.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./dist", false)
+    ///         .await?
+    ///         .pristine(true)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn pristine(mut self, enabled: bool) -> Self {
+        self.server_options.pristine = enabled;
+        self
+    }
+
+    /// Set how often the WebSocket connections are pinged, so that idle connections are not
+    /// dropped by NATs or proxies. A client which does not answer until the next ping is
+    /// disconnected. Defaults to 30s, and `Duration::ZERO` disables the pings.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .ping_interval(Duration::from_secs(10))
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn ping_interval(mut self, interval: Duration) -> Self {
+        self.server_options.ping_interval = interval;
+        self
+    }
+
+    /// Call the callback for each request once it is answered, e.g. to print a line per
+    /// request. The requests of live-server's own script and assets are left out. Each
+    /// call adds a callback, which runs after those added before.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .on_request(|request| {
+    ///             println!("{} {} {}", request.method, request.uri, request.status);
+    ///         })
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn on_request<F: Fn(&RequestSummary) + Send + Sync + 'static>(
+        mut self,
+        callback: F,
+    ) -> Self {
+        self.server_options
+            .on_request
+            .push(stats::RequestHook(Arc::new(callback)));
+        self
+    }
+
+    /// Run the async callback for each request once it is answered, e.g. to show the
+    /// activity in the UI of a wrapper tool. The callback runs in its own task, so that
+    /// it does not hold the response back. The requests of live-server's own script and
+    /// assets are left out.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .on_request_async(|request| async move {
+    ///             println!("{} {} in {:?}", request.path(), request.status, request.duration);
+    ///         })
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn on_request_async<F, Fut>(self, callback: F) -> Self
+    where
+        F: Fn(RequestSummary) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_request(move |request| {
+            tokio::spawn(callback(request.clone()));
+        })
+    }
+
+    /// Call the callback each time the pages are told to reload, with the changed files,
+    /// which are none when the reload is forced, e.g. by [`reload`]. Each call adds a
+    /// callback, which runs after those added before.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .on_reload(|changes| {
+    ///             for change in changes {
+    ///                 println!("{} {}", change.kind(), change.path());
+    ///             }
+    ///         })
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn on_reload<F: Fn(&[Change]) + Send + Sync + 'static>(mut self, callback: F) -> Self {
+        self.server_options
+            .on_reload
+            .push(watcher::ReloadHook(Arc::new(callback)));
+        self
+    }
+
+    /// Set how many reload messages are kept for the clients which have not received
+    /// them yet. A client which falls further behind, e.g. a throttled background tab,
+    /// gets a single reload in place of the messages it missed. Defaults to 16.
+    ///
+    /// [`Listener::start`] fails if the capacity is zero.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .channel_capacity(64)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn channel_capacity(mut self, capacity: usize) -> Self {
+        self.server_options.channel_capacity = capacity;
+        self
+    }
+
+    /// Set the URL the injected script connects to, e.g. `wss://example.com/live-server-ws`.
+    /// By default it is derived from the location of the page, which works through most
+    /// reverse proxies and tunnels as long as they forward `/live-server-ws`.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .ws_url("wss://example.com/live-server-ws")
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn ws_url<S: Into<String>>(mut self, url: S) -> Self {
+        self.server_options.ws_url = Some(url.into());
+        self
+    }
+
+    /// Serve another directory at the URL prefix, e.g. `../shared/assets` at `/assets`,
+    /// and reload when files in it change. It takes precedence over the root for the
+    /// paths under the prefix, and can be called multiple times to add more directories.
+    /// The leading `/` of the prefix is optional, and a prefix of `/` replaces the root.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./public", true)
+    ///         .await?
+    ///         .mount("/assets", "../shared/assets")
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn mount<S: AsRef<str>, P: Into<PathBuf>>(mut self, prefix: S, dir: P) -> Self {
+        let prefix = url_prefix(prefix.as_ref());
+        self.server_options.mounts.push((prefix, dir.into()));
+        self
+    }
+
+    /// Wait before responding to every request, e.g. to see the loading states of the pages
+    /// as on a slow network. Use [`Listener::delay_for`] to only delay some of the paths.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .delay(Duration::from_millis(300))
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn delay(mut self, duration: Duration) -> Self {
+        self.unchecked.delays.push((None, duration));
+        self
+    }
+
+    /// Wait before responding to the requests whose path, relative to the root, matches the
+    /// glob pattern, e.g. `api/**` or `*.json`. It takes precedence over [`Listener::delay`]
+    /// when called before it, and the first matching pattern is used.
+    ///
+    /// [`Listener::start`] fails if the pattern is not a valid glob.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .delay_for("api/**", Duration::from_secs(2))
+    ///         .delay(Duration::from_millis(100))
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn delay_for<S: AsRef<str>>(mut self, pattern: S, duration: Duration) -> Self {
+        let pattern = pattern.as_ref().to_string();
+        self.unchecked.delays.push((Some(pattern), duration));
+        self
+    }
+
+    /// Limit the rate the responses are sent at, in bytes per second, e.g. to watch
+    /// progressive images and videos load as on a slow network.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .throttle(125_000) // 1 Mbps
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn throttle(mut self, bytes_per_second: u64) -> Self {
+        self.server_options.throttle = Some(bytes_per_second);
+        self
+    }
+
+    /// Fail a share of the requests on purpose, from `0.0` to `1.0`, to test the retries and
+    /// the error handling of the pages. Half of the failures respond with
+    /// `500 Internal Server Error`, and the other half hang for 30 seconds before giving up
+    /// with `504 Gateway Timeout`.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .chaos(0.05)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn chaos(mut self, rate: f64) -> Self {
+        self.server_options.chaos = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Replace the `{{ env:NAME }}` placeholders in the pages and scripts with the
+    /// environment variables when serving them, e.g. `{{ env:PUBLIC_API_URL }}`, so that
+    /// each developer can point the site to their own endpoints without a build step.
+    ///
+    /// Only the variables starting with `PUBLIC_` are substituted, so that the other ones,
+    /// such as the tokens and keys of the shell, are never sent to the browsers.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .env_vars(true)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn env_vars(mut self, enabled: bool) -> Self {
+        self.server_options.env_vars = enabled;
+        self
+    }
+
+    /// Expand the server-side includes of the pages, such as
+    /// `<!--#include file="header.html" -->` or `<!--#include virtual="/footer.html" -->`,
+    /// so that the sites built on them can be previewed. Editing an included file reloads
+    /// the pages as any other file. The files which would not be served, such as the hidden
+    /// dotfiles, are not included either.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .ssi(true)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn ssi(mut self, enabled: bool) -> Self {
+        self.server_options.ssi = enabled;
+        self
+    }
+
+    /// Find the files ignoring the case of the request path, so that `/Logo.PNG` serves
+    /// `logo.png` as on macOS and Windows. This helps to debug the sites made on those
+    /// systems. The exact match is still preferred.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .case_insensitive(true)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn case_insensitive(mut self, enabled: bool) -> Self {
+        self.server_options.case_insensitive = enabled;
+        self
+    }
 
-static WATCH: OnceCell<bool> = OnceCell::const_new();
-static ADDR: OnceCell<String> = OnceCell::const_new();
-static ROOT: OnceCell<PathBuf> = OnceCell::const_new();
-static TX: OnceCell<broadcast::Sender<()>> = OnceCell::const_new();
+    /// Serve the files and directories behind symlinks pointing outside of the root.
+    /// They are refused by default, so that a symlink cannot expose the rest of the disk.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .follow_symlinks(true)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn follow_symlinks(mut self, enabled: bool) -> Self {
+        self.server_options.follow_symlinks = enabled;
+        self
+    }
 
-pub struct Listener {
-    tcp_listener: TcpListener,
-    router: Router,
-    root_path: PathBuf,
-    watcher: Option<Watcher>,
-}
+    /// Serve the page for the responses with the status code, e.g. `404` or `500`, instead of
+    /// the built-in error page. The path is relative to the root, and the page gets the same
+    /// scripts injected as the other pages. It can be called multiple times for more codes.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .error_page(404, "errors/404.html")
+    ///         .error_page(500, "errors/500.html")
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn error_page<P: Into<PathBuf>>(mut self, status: u16, path: P) -> Self {
+        self.server_options.error_pages.insert(status, path.into());
+        self
+    }
 
-impl Listener {
-    /// Start live-server.
+    /// Serve the site under the base path instead of `/`, e.g. `/repo/` like a GitHub Pages
+    /// project site, so that links assuming the site is at `/` break as they would there.
+    /// Requests for `/` are redirected to the base path.
     ///
     /// ```
     /// use live_server::listen;
     ///
     /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
-    ///     listen("127.0.0.1:8080", "./", true).await?.start().await
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .base("/repo/")
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn base<S: AsRef<str>>(mut self, path: S) -> Self {
+        self.server_options.base = match path.as_ref().trim_matches('/') {
+            "" => String::new(),
+            path => util::encode_path(&format!("/{path}")),
+        };
+        self
+    }
+
+    /// Rewrite the request paths matching the regex to another path before resolving the file,
+    /// e.g. `/v1/(.*)` to `/latest/$1`. The pattern has to match the whole path, and the
+    /// replacement can refer to its capture groups. The first matching rule is applied.
+    ///
+    /// [`Listener::start`] fails if the pattern is not a valid regex.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .rewrite("/v1/(.*)", "/latest/$1")
+    ///         .rewrite("/app/.*", "/app/index.html")
+    ///         .start()
+    ///         .await
     /// }
     /// ```
-    pub async fn start(self) -> Result<(), Box<dyn Error>> {
-        ROOT.set(self.root_path.clone())?;
-        let (tx, _) = broadcast::channel(16);
-        TX.set(tx)?;
+    pub fn rewrite<P: AsRef<str>, R: Into<String>>(mut self, pattern: P, replacement: R) -> Self {
+        let pattern = pattern.as_ref().to_string();
+        self.unchecked.rewrites.push((pattern, replacement.into()));
+        self
+    }
 
-        let server_future = tokio::spawn(server::serve(self.tcp_listener, self.router));
+    /// Answer the requests under the URL prefix with the JSON files in the directory, e.g.
+    /// `/api/users` with `mocks/users.json`, to demo the pages without a backend. A file with
+    /// the method, like `users.POST.json`, is preferred for the requests with that method.
+    /// The pages reload when the files change. Use [`Listener::delay_for`] to make the
+    /// responses slower.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .mock("/api", "./mocks")
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn mock<S: AsRef<str>, P: Into<PathBuf>>(mut self, prefix: S, dir: P) -> Self {
+        let prefix = url_prefix(prefix.as_ref());
+        self.server_options.mocks.push((prefix, dir.into()));
+        self
+    }
 
-        if let Some(watcher) = self.watcher {
-            let watcher_future = tokio::spawn(watcher::watch(self.root_path, watcher));
-            tokio::try_join!(watcher_future, server_future)?;
-        } else {
-            tokio::try_join!(server_future)?;
-        }
+    /// Serve the content at the URL path without a file on the disk, taking precedence over
+    /// the files under the root. Use [`set_virtual_file`] to change it after the server started.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .virtual_file("/robots.txt", "User-agent: *\nDisallow: /\n")
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn virtual_file<P: AsRef<str>, C: Into<Vec<u8>>>(mut self, path: P, content: C) -> Self {
+        let content = virtual_files::Content::Bytes(content.into().into());
+        self.virtual_files
+            .push((path.as_ref().to_string(), content));
+        self
+    }
 
-        Ok(())
+    /// Serve the content returned by the closure at the URL path, calling it for each request.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .virtual_route("/time.txt", || format!("{:?}", std::time::SystemTime::now()))
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn virtual_route<P, F, C>(mut self, path: P, generator: F) -> Self
+    where
+        P: AsRef<str>,
+        F: Fn() -> C + Send + Sync + 'static,
+        C: Into<Vec<u8>>,
+    {
+        let content = virtual_files::Content::Generator(Arc::new(move || generator().into()));
+        self.virtual_files
+            .push((path.as_ref().to_string(), content));
+        self
+    }
+
+    /// Serve the files with the extension as the MIME type, overriding the guessed one,
+    /// e.g. `gltf` as `model/gltf+json`. It can be called multiple times to add more types.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .mime_type("gltf", "model/gltf+json")
+    ///         .mime_type(".wgsl", "text/wgsl")
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn mime_type<E: AsRef<str>, M: Into<String>>(mut self, extension: E, mime: M) -> Self {
+        let extension = extension.as_ref().trim_start_matches('.').to_lowercase();
+        self.server_options
+            .mime_types
+            .insert(extension, mime.into());
+        self
+    }
+
+    /// Append `; charset=utf-8` to the `Content-Type` of text files, such as HTML, CSS and
+    /// JavaScript, so that pages without `<meta charset>` are decoded correctly.
+    /// Enabled by default.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .charset(false)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn charset(mut self, enabled: bool) -> Self {
+        self.server_options.charset = enabled;
+        self
+    }
+
+    /// Serve `about.html` for `/about` when the literal path does not exist, so that the
+    /// extensionless URLs of static hosts work locally as well. Directories are still
+    /// resolved to their `index.html`.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .clean_urls(true)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn clean_urls(mut self, enabled: bool) -> Self {
+        self.server_options.clean_urls = enabled;
+        self
+    }
+
+    /// Redirect `/docs` to `/docs/` with `301 Moved Permanently` when `docs` is a directory,
+    /// so that the relative links of its `index.html` resolve correctly. Enabled by default.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .trailing_slash_redirect(false)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn trailing_slash_redirect(mut self, enabled: bool) -> Self {
+        self.server_options.trailing_slash_redirect = enabled;
+        self
+    }
+
+    /// Serve the dotfiles, such as `.env` or `.git`, and show them in the directory listing.
+    /// They are hidden by default, so that they are not exposed on the network by accident.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .show_dotfiles(true)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn show_dotfiles(mut self, enabled: bool) -> Self {
+        self.server_options.show_dotfiles = enabled;
+        self
+    }
+
+    /// Refuse the requests for dotfiles, such as `/.git/config` or `/.env`, with
+    /// `403 Forbidden`, whether or not they are shown in the directory listing.
+    /// It is enabled by default when listening on an address other than loopback.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .deny_dotfiles(true)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn deny_dotfiles(mut self, enabled: bool) -> Self {
+        self.server_options.deny_dotfiles = enabled;
+        self
+    }
+
+    /// Set the language of the directory listing and the error page, e.g. `de` or `zh-CN`.
+    /// By default it is chosen by the `Accept-Language` header of the request.
+    /// English, Chinese, Japanese, German, French and Spanish are supported.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .lang("ja")
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn lang<S: Into<String>>(mut self, lang: S) -> Self {
+        self.server_options.lang = Some(lang.into());
+        self
+    }
+
+    /// Show a small badge in the corner of the page telling whether live reload is connected.
+    /// It can be dismissed by clicking on it.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .status_badge(true)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn status_badge(mut self, enabled: bool) -> Self {
+        self.server_options.status_badge = enabled;
+        self
+    }
+
+    /// Set where the state of the server is stored, which is in memory by default.
+    ///
+    /// ```
+    /// use live_server::{listen, DiskStorage};
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .storage(DiskStorage::new(".live-server")?)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn storage<S: Storage + 'static>(mut self, storage: S) -> Self {
+        self.storage = Box::new(storage);
+        self
+    }
+
+    /// Enable the admin API under `/_live-server/admin`, authenticated by the
+    /// `Authorization: Bearer <token>` header.
+    ///
+    /// The built-in templates and assets can then be replaced at runtime, e.g.
+    /// `PUT /_live-server/admin/assets/templates/listing.html`, and restored with `DELETE`.
+    /// `GET /_live-server/admin/assets` lists the names of the replaceable files.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./", true)
+    ///         .await?
+    ///         .admin_token("secret")
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn admin_token<S: Into<String>>(mut self, token: S) -> Self {
+        self.admin_token = Some(token.into());
+        self
+    }
+
+    /// Add buttons to rename and delete the files and the directories to the directory
+    /// listing, which ask for the token of [`Listener::admin_token`]. Without the token,
+    /// nothing can be renamed or deleted.
+    ///
+    /// The admin API then also has `PATCH /_live-server/admin/files/<path>` with a
+    /// `{"name": "new name"}` body to rename a file within its directory, and
+    /// `DELETE /_live-server/admin/files/<path>` to delete a file or a whole directory.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("0.0.0.0:8080", "./fixtures", true)
+    ///         .await?
+    ///         .admin_token("secret")
+    ///         .manage(true)
+    ///         .start()
+    ///         .await
+    /// }
+    /// ```
+    pub fn manage(mut self, enabled: bool) -> Self {
+        self.server_options.manage = enabled;
+        self
+    }
+
+    /// Return the address the server is bound to, with the port chosen by the system
+    /// when listening on port 0, e.g. for a test harness to build its own URLs.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let listener = listen("127.0.0.1:0", "./", true).await?;
+    ///     let port = listener.local_addr()?.port();
+    ///     println!("Serving on port {port}");
+    ///     listener.start().await
+    /// }
+    /// ```
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.tcp_listener.local_addr()
+    }
+
+    /// Return the links the server can be reached at, e.g. `http://localhost:8080` and
+    /// `http://192.168.1.2:8080` for each network interface when listening on `0.0.0.0`,
+    /// or only the address it is bound to otherwise.
+    ///
+    /// ```
+    /// use live_server::listen;
+    ///
+    /// async fn serve() {
+    ///     let listener = listen("0.0.0.0:8080", "./", true).await.unwrap();
+    ///     for link in listener.links().unwrap() {
+    ///         println!("{link}");
+    ///     }
+    /// }
+    /// ```
+    pub fn links(&self) -> std::io::Result<Vec<String>> {
+        let base = match self.server_options.base.as_str() {
+            "" => String::new(),
+            base => format!("{base}/"),
+        };
+        Ok(server::links(self.local_addr()?, &base))
     }
 
     /// Return the link of the server, like `http://127.0.0.1:8080`.
@@ -97,9 +1713,13 @@ impl Listener {
             false => host,
         };
 
+        let base = match self.server_options.base.as_str() {
+            "" => String::new(),
+            base => format!("{base}/"),
+        };
         Ok(match host {
-            IpAddr::V4(host) => format!("http://{host}:{port}"),
-            IpAddr::V6(host) => format!("http://[{host}]:{port}"),
+            IpAddr::V4(host) => format!("http://{host}:{port}{base}"),
+            IpAddr::V6(host) => format!("http://[{host}]:{port}{base}"),
         })
     }
 }
@@ -113,20 +1733,68 @@ impl Listener {
 ///     listen("127.0.0.1:8080", "./", true).await?.start().await
 /// }
 /// ```
+///
+/// The root can also be a single file, which is then served at `/` along with the other
+/// files in its directory, and only changes to the file itself trigger a reload.
+///
+/// ```
+/// use live_server::listen;
+///
+/// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+///     listen("127.0.0.1:8080", "./presentation.html", true).await?.start().await
+/// }
+/// ```
+///
+/// A `.zip` archive as the root is served from the inside without unpacking it, and
+/// reloaded when the archive changes.
+///
+/// ```
+/// use live_server::listen;
+///
+/// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+///     listen("127.0.0.1:8080", "./site.zip", true).await?.start().await
+/// }
+/// ```
 pub async fn listen<A: Into<String>, R: Into<PathBuf>>(
     addr: A,
     root: R,
     watch: bool,
-) -> Result<Listener, String> {
-    WATCH.set(watch).unwrap();
+) -> Result<Listener, ListenError> {
+    create_listener_for(addr.into(), root.into(), watch, false).await
+}
 
-    let tcp_listener = create_listener(addr.into()).await?;
+async fn create_listener_for(
+    addr: String,
+    root: PathBuf,
+    watch: bool,
+    dual_stack: bool,
+) -> Result<Listener, ListenError> {
+    let tcp_listener = create_listener(addr, dual_stack)
+        .await
+        .map_err(ListenError::Bind)?;
     let router = create_server();
 
-    let root = root.into();
-
-    let root_path = match tokio::fs::canonicalize(&root).await {
+    let root_path = match canonicalize(&root).await {
         Ok(path) => path,
+        // Wait for the root to be created, e.g. `dist/` before the first build.
+        Err(err) if err.kind() == ErrorKind::NotFound => match absolute_missing(&root).await {
+            Ok(path) => {
+                log::warn!(
+                    "{} does not exist, waiting for it to be created",
+                    path_to_string_but_readable(&path)
+                );
+                path
+            }
+            Err(err) => {
+                let err_msg = format!(
+                    "Failed to get absolute path of {:?}: {}",
+                    path_to_string_but_readable(root),
+                    err
+                );
+                log::error!("{}", err_msg);
+                return Err(ListenError::Root(err_msg));
+            }
+        },
         Err(err) => {
             let err_msg = format!(
                 "Failed to get absolute path of {:?}: {}",
@@ -134,26 +1802,224 @@ pub async fn listen<A: Into<String>, R: Into<PathBuf>>(
                 err
             );
             log::error!("{}", err_msg);
-            return Err(err_msg);
+            return Err(ListenError::Root(err_msg));
         }
     };
 
     log::info!("Listening on {}", path_to_string_but_readable(&root_path));
 
-    let watcher = if watch {
-        Some(create_watcher().await?)
-    } else {
-        None
+    // Serve a single file at `/`, and the other files in its directory as they are,
+    // so that its relative links keep working. A zip archive is served from the inside.
+    let mut watch_options = WatchOptions::default();
+    let mut server_options = ServerOptions {
+        deny_dotfiles: is_exposed(&tcp_listener),
+        ..Default::default()
+    };
+    let is_zip = root_path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"));
+    let root_path = match root_path.parent() {
+        Some(dir) if root_path.is_file() && is_zip => {
+            watch_options.file = Some(root_path.clone());
+            server_options.archive = Some(root_path.clone());
+            server_options.disk = false;
+            dir.to_path_buf()
+        }
+        Some(dir) if root_path.is_file() => {
+            watch_options.file = Some(root_path.clone());
+            server_options.index_file = Some(root_path.clone());
+            dir.to_path_buf()
+        }
+        _ => root_path,
     };
 
     Ok(Listener {
         tcp_listener,
         router,
         root_path,
-        watcher,
+        watch,
+        watch_options,
+        server_options,
+        storage: Box::new(MemoryStorage::new()),
+        admin_token: None,
+        shutdown_signal: None,
+        bundle: None,
+        virtual_files: Vec::new(),
+        unchecked: Unchecked::default(),
+    })
+}
+
+/// The options of [`listen_with_options`], so that new options do not change the
+/// arguments of [`listen`]. The other options are set on the returned [`Listener`].
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Watch the files and reload the pages when they change. Defaults to `true`.
+    pub watch: bool,
+    /// The glob patterns of the changes to ignore, as with [`Listener::ignore`].
+    pub ignore: Vec<String>,
+    /// Ignore the changes to the files ignored by git, as with [`Listener::gitignore`].
+    pub gitignore: bool,
+    /// Serve `/about` from `about.html`, as with [`Listener::clean_urls`].
+    pub clean_urls: bool,
+    /// Serve and list the dotfiles, as with [`Listener::show_dotfiles`].
+    pub show_dotfiles: bool,
+    /// Accept the IPv4 clients as well when listening on `[::]`, which some systems,
+    /// e.g. Windows, do not by default.
+    pub dual_stack: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            watch: true,
+            ignore: Vec::new(),
+            gitignore: false,
+            clean_urls: false,
+            show_dotfiles: false,
+            dual_stack: false,
+        }
+    }
+}
+
+/// Create live-server listener with the options, like [`listen`].
+///
+/// ```
+/// use live_server::{listen_with_options, Options};
+///
+/// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+///     let options = Options {
+///         ignore: vec!["*.tmp".to_string()],
+///         clean_urls: true,
+///         ..Default::default()
+///     };
+///     listen_with_options("127.0.0.1:8080", "./", options)
+///         .await?
+///         .start()
+///         .await
+/// }
+/// ```
+pub async fn listen_with_options<A: Into<String>, R: Into<PathBuf>>(
+    addr: A,
+    root: R,
+    options: Options,
+) -> Result<Listener, ListenError> {
+    let mut listener =
+        create_listener_for(addr.into(), root.into(), options.watch, options.dual_stack)
+            .await?
+            .gitignore(options.gitignore)
+            .clean_urls(options.clean_urls)
+            .show_dotfiles(options.show_dotfiles);
+    for pattern in options.ignore {
+        listener = listener.ignore(pattern);
+    }
+    Ok(listener)
+}
+
+/// Create live-server listener serving the bundled files, e.g. the documentation embedded
+/// in the binary with `include_bytes!`, without touching the filesystem.
+///
+/// The files are keyed by their URL path, and `index.html` is served for the directories.
+/// There is nothing to watch, but the pages still reload when [`set_virtual_file`] changes
+/// a file.
+///
+/// ```
+/// use std::collections::HashMap;
+/// use live_server::listen_bundle;
+///
+/// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+///     let files = HashMap::from([
+///         ("/index.html", "<h1>Docs</h1>"),
+///         ("/style.css", "h1 { color: teal; }"),
+///     ]);
+///     listen_bundle("127.0.0.1:8080", files).await?.start().await
+/// }
+/// ```
+pub async fn listen_bundle<A, I, P, C>(addr: A, files: I) -> Result<Listener, ListenError>
+where
+    A: Into<String>,
+    I: IntoIterator<Item = (P, C)>,
+    P: AsRef<str>,
+    C: Into<Vec<u8>>,
+{
+    let tcp_listener = create_listener(addr.into(), false)
+        .await
+        .map_err(ListenError::Bind)?;
+    let router = create_server();
+
+    let files = files
+        .into_iter()
+        .map(|(path, content)| (path.as_ref().to_string(), content.into()))
+        .collect();
+
+    let server_options = ServerOptions {
+        disk: false,
+        deny_dotfiles: is_exposed(&tcp_listener),
+        ..Default::default()
+    };
+
+    Ok(Listener {
+        tcp_listener,
+        router,
+        root_path: PathBuf::new(),
+        watch: false,
+        watch_options: WatchOptions::default(),
+        server_options,
+        storage: Box::new(MemoryStorage::new()),
+        admin_token: None,
+        shutdown_signal: None,
+        bundle: Some(files),
+        virtual_files: Vec::new(),
+        unchecked: Unchecked::default(),
     })
 }
 
+/// The prefix of a mount or a mock as matched against the paths, e.g. `/assets` for
+/// `assets/`, or an empty one for `/` so that it matches every path.
+fn url_prefix(prefix: &str) -> String {
+    match prefix.trim_matches('/') {
+        "" => String::new(),
+        prefix => format!("/{prefix}"),
+    }
+}
+
+/// Whether the listener is reachable from other machines, i.e. not bound to loopback.
+fn is_exposed(tcp_listener: &TcpListener) -> bool {
+    tcp_listener
+        .local_addr()
+        .map_or(true, |addr| !addr.ip().is_loopback())
+}
+
+/// Get the absolute path of the file, without the `\\?\` prefix on Windows unless it is
+/// needed for a long path, so that it matches the paths reported by the watcher.
+async fn canonicalize<P: AsRef<Path>>(path: P) -> std::io::Result<PathBuf> {
+    let path = path.as_ref().to_path_buf();
+    tokio::task::spawn_blocking(move || dunce::canonicalize(path)).await?
+}
+
+/// Get the absolute path of the file which does not exist yet, canonicalizing its
+/// closest existing ancestor so that it matches the paths reported once it exists.
+async fn absolute_missing(path: &Path) -> std::io::Result<PathBuf> {
+    let path = std::path::absolute(path)?;
+    let mut missing = vec![];
+    let mut ancestor = path.as_path();
+    while let (Some(parent), Some(name)) = (ancestor.parent(), ancestor.file_name()) {
+        missing.push(name);
+        if let Ok(parent) = canonicalize(parent).await {
+            return Ok(missing
+                .iter()
+                .rev()
+                .fold(parent, |path, name| path.join(name)));
+        }
+        ancestor = parent;
+    }
+    Ok(path)
+}
+
 fn path_to_string_but_readable<P: AsRef<Path>>(path: P) -> String {
-    path.as_ref().to_slash_lossy().replace("\\\\?\\", "")
+    let path = path.as_ref().to_slash_lossy();
+    // Windows paths may keep the verbatim prefix when they are too long to go without it.
+    match path.strip_prefix(r"\\?\UNC\") {
+        Some(unc) => format!("//{}", unc.replace('\\', "/")),
+        None => path.replace(r"\\?\", ""),
+    }
 }
@@ -1,19 +1,108 @@
 use axum::http::{header, HeaderValue, StatusCode};
 use axum::{body::Body, http::HeaderMap};
 use chrono::{DateTime, Local};
+use serde::Deserialize;
 use std::path::{Path, StripPrefixError};
 use std::{path::PathBuf, time::SystemTime};
 use tokio::fs::DirEntry;
 
-use crate::path_to_string_but_readable;
+use crate::admin::ADMIN_TOKEN;
+use crate::i18n::{translate, Strings};
+use crate::preview::PreviewKind;
 use crate::server::internal_err;
 use crate::static_files::{
     get_dir_link_svg, get_dir_svg, get_entry_html, get_file_link_svg, get_file_svg,
     get_listing_html, get_unknown_svg,
 };
+use crate::util::{encode_path, escape_html, is_hidden, render};
+use crate::{path_to_string_but_readable, OPTIONS};
+
+/// How the entries of the directory listing are sorted, e.g. `?sort=modified&order=desc`.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct ListingQuery {
+    #[serde(default)]
+    sort: SortKey,
+    #[serde(default)]
+    order: Option<SortOrder>,
+    /// The page to show, starting from 1.
+    #[serde(default)]
+    page: Option<usize>,
+    /// The number of entries per page.
+    #[serde(default)]
+    limit: Option<usize>,
+    /// Download the directory as a zip archive instead, e.g. `?zip=1`.
+    #[serde(default)]
+    zip: Option<String>,
+}
+
+impl ListingQuery {
+    pub(crate) fn wants_zip(&self) -> bool {
+        self.zip.is_some()
+    }
+}
+
+/// The number of entries per page, so that huge directories do not lock up the browser.
+const DEFAULT_LIMIT: usize = 1000;
 
-pub async fn serve_directory_listing(root: &Path, dir: PathBuf) -> (StatusCode, HeaderMap, Body) {
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Modified,
+}
+
+impl SortKey {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Modified => "modified",
+        }
+    }
+
+    /// Names are sorted alphabetically by default, sizes and times largest first.
+    fn default_order(&self) -> SortOrder {
+        match self {
+            SortKey::Name => SortOrder::Asc,
+            SortKey::Size | SortKey::Modified => SortOrder::Desc,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+
+    fn reverse(&self) -> Self {
+        match self {
+            SortOrder::Asc => SortOrder::Desc,
+            SortOrder::Desc => SortOrder::Asc,
+        }
+    }
+}
+
+pub async fn serve_directory_listing(
+    prefix: &str,
+    root: &Path,
+    dir: PathBuf,
+    query: ListingQuery,
+    strings: &Strings,
+) -> (StatusCode, HeaderMap, Body) {
     let dir_string = path_to_string_but_readable(&dir);
+    let breadcrumbs = breadcrumbs(prefix, root, &dir);
 
     let mut headers = HeaderMap::new();
     headers.append(
@@ -26,6 +115,7 @@ pub async fn serve_directory_listing(root: &Path, dir: PathBuf) -> (StatusCode,
         Err(e) => return internal_err(e),
     };
 
+    let show_dotfiles = OPTIONS.get().unwrap().show_dotfiles;
     let mut entries = vec![];
     let mut rows = String::new();
 
@@ -33,24 +123,81 @@ pub async fn serve_directory_listing(root: &Path, dir: PathBuf) -> (StatusCode,
         Ok(entry) => entry,
         Err(e) => return internal_err(e),
     } {
-        let entry_type = get_entry_type(&entry.path()).await;
+        if !show_dotfiles && is_hidden(&entry.file_name().to_string_lossy()) {
+            continue;
+        }
+        // The type comes with the names on most platforms, so only the symbolic links are
+        // followed, and the other entries are only read for the page shown.
+        let entry_type = match entry.file_type().await {
+            Ok(file_type) if file_type.is_dir() => EntryType::Dir,
+            Ok(file_type) if file_type.is_file() => EntryType::File,
+            _ => get_entry_type(&entry.path()).await,
+        };
+        let metadata = match query.sort {
+            SortKey::Name => None,
+            SortKey::Size | SortKey::Modified => entry.metadata().await.ok(),
+        };
 
-        entries.push((entry, entry_type));
+        entries.push((entry, entry_type, metadata));
     }
 
-    entries.sort_by(|(_, a), (_, b)| a.value().cmp(&b.value()));
+    let order = query.order.unwrap_or(query.sort.default_order());
+    entries.sort_by(|(a, a_type, a_metadata), (b, b_type, b_metadata)| {
+        let ordering = match query.sort {
+            SortKey::Name => a.file_name().cmp(&b.file_name()),
+            SortKey::Size => a_metadata
+                .as_ref()
+                .map(|m| m.len())
+                .cmp(&b_metadata.as_ref().map(|m| m.len())),
+            SortKey::Modified => a_metadata
+                .as_ref()
+                .and_then(|m| m.modified().ok())
+                .cmp(&b_metadata.as_ref().and_then(|m| m.modified().ok())),
+        };
+        let ordering = match order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        };
+        // Keep the directories before the files.
+        a_type.value().cmp(&b_type.value()).then(ordering)
+    });
+
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).max(1);
+    let pages = entries.len().div_ceil(limit).max(1);
+    let page = query.page.unwrap_or(1).clamp(1, pages);
+
+    // The buttons of `Listener::manage`, which are of no use without the admin token.
+    let actions = match OPTIONS.get().unwrap().manage && ADMIN_TOKEN.get().is_some() {
+        true => concat!(
+            r#"<button data-action="rename" title="{{ t_rename }}">✎</button>"#,
+            r#"<button data-action="delete" title="{{ t_delete }}">×</button>"#,
+        ),
+        false => "",
+    };
 
-    for (entry, entry_type) in entries {
+    let entry_html = match get_entry_html().await {
+        Ok(template) => template,
+        Err(e) => return internal_err(e),
+    };
+
+    for (entry, entry_type, metadata) in entries.into_iter().skip((page - 1) * limit).take(limit) {
+        let metadata = match metadata {
+            Some(metadata) => Some(metadata),
+            None => entry.metadata().await.ok(),
+        };
         let name = entry.file_name();
         let name = name.to_string_lossy();
 
-        let path = match entry_to_path(&entry, root) {
+        let mut path = match entry_to_path(&entry, prefix, root) {
             Ok(entry) => entry,
             Err(e) => return internal_err(e),
         };
+        if entry_type.is_dir() || matches!(entry_type, EntryType::DirLink) {
+            path.push('/');
+        }
 
-        let (bytes, modified) = match entry.metadata().await {
-            Ok(metadata) => (
+        let (bytes, modified) = match metadata {
+            Some(metadata) => (
                 if !entry_type.is_dir() {
                     Some(format_file_size(metadata.len()))
                 } else {
@@ -61,13 +208,8 @@ pub async fn serve_directory_listing(root: &Path, dir: PathBuf) -> (StatusCode,
             _ => (None, None),
         };
 
-        let mut template = match get_entry_html().await {
-            Ok(template) => template,
-            Err(e) => return internal_err(e),
-        };
-
-        template = render(
-            template,
+        let mut template = render(
+            entry_html.clone(),
             "icon",
             match entry_type.to_icon().await {
                 Ok(icon) => icon,
@@ -75,6 +217,12 @@ pub async fn serve_directory_listing(root: &Path, dir: PathBuf) -> (StatusCode,
             },
         );
 
+        let preview = match PreviewKind::of(&entry.path()) {
+            Some(_) if !entry_type.is_dir() => " data-preview",
+            _ => "",
+        };
+        template = render(template, "preview", preview);
+        template = render(template, "actions", actions);
         template = render(template, "path", path);
         template = render(template, "name", escape_html(name));
         template = render(template, "size", escape_html(bytes.unwrap_or_default()));
@@ -92,15 +240,93 @@ pub async fn serve_directory_listing(root: &Path, dir: PathBuf) -> (StatusCode,
         Err(e) => return internal_err(e),
     };
 
+    template = render(template, "base", &OPTIONS.get().unwrap().base);
     template = render(template, "directory", escape_html(dir_string));
+    template = render(template, "breadcrumbs", breadcrumbs);
     template = render(template, "entries", rows);
+    for key in [SortKey::Name, SortKey::Size, SortKey::Modified] {
+        // Clicking the current column again reverses the order.
+        let (link_order, arrow) = if key == query.sort {
+            let arrow = match order {
+                SortOrder::Asc => " ▲",
+                SortOrder::Desc => " ▼",
+            };
+            (order.reverse(), arrow)
+        } else {
+            (key.default_order(), "")
+        };
+        let link = format!("?sort={}&amp;order={}", key.as_str(), link_order.as_str());
+        template = render(template, &format!("sort_{}", key.as_str()), link);
+        template = render(template, &format!("arrow_{}", key.as_str()), arrow);
+    }
+
+    let pagination = if pages > 1 {
+        let link = |page: usize, text: &str| {
+            let mut href = format!(
+                "?sort={}&amp;order={}&amp;page={}",
+                query.sort.as_str(),
+                order.as_str(),
+                page
+            );
+            if let Some(limit) = query.limit {
+                href.push_str(&format!("&amp;limit={limit}"));
+            }
+            format!(r#"<a href="{href}">{text}</a>"#)
+        };
+        let previous = if page > 1 {
+            link(page - 1, strings.previous)
+        } else {
+            String::new()
+        };
+        let next = if page < pages {
+            link(page + 1, strings.next)
+        } else {
+            String::new()
+        };
+        let current = strings.page_of(page, pages);
+        format!(r#"<nav id="pagination">{previous}<span>{current}</span>{next}</nav>"#)
+    } else {
+        String::new()
+    };
+    template = render(template, "pagination", pagination);
+    template = render(template, "upload", crate::upload::form());
+    template = translate(template, strings);
 
     let body = Body::from(template);
 
     (StatusCode::OK, headers, body)
 }
 
-fn entry_to_path(entry: &DirEntry, root: &Path) -> Result<String, StripPrefixError> {
+/// Render a link for the root, served at `prefix`, and each directory down to `dir`.
+fn breadcrumbs(prefix: &str, root: &Path, dir: &Path) -> String {
+    let root_name = root
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "/".to_string());
+    let mut href = encode_path(&format!("{prefix}/"));
+    let mut html = format!(
+        r#"<a href="{}">{}</a>"#,
+        escape_html(&href),
+        escape_html(root_name)
+    );
+
+    if let Ok(path) = dir.strip_prefix(root) {
+        for component in path.components() {
+            let name = component.as_os_str().to_string_lossy();
+            href.push_str(&encode_path(&name));
+            href.push('/');
+            html.push_str(&format!(
+                r#"<span>/</span><a href="{}">{}</a>"#,
+                escape_html(&href),
+                escape_html(name)
+            ));
+        }
+    }
+
+    html
+}
+
+fn entry_to_path(entry: &DirEntry, prefix: &str, root: &Path) -> Result<String, StripPrefixError> {
     let path = entry.path();
 
     let path = if let Ok(p) = path.strip_prefix(root) {
@@ -109,11 +335,11 @@ fn entry_to_path(entry: &DirEntry, root: &Path) -> Result<String, StripPrefixErr
         path
     };
 
-    Ok(format!("/{}", path_to_string_but_readable(path)))
-}
-
-fn render<S: AsRef<str>>(template: String, var_name: &str, value: S) -> String {
-    template.replace(&format!("{{{{ {} }}}}", var_name), value.as_ref())
+    Ok(encode_path(&format!(
+        "{}/{}",
+        prefix,
+        path_to_string_but_readable(path)
+    )))
 }
 
 fn format_system_time(system_time: SystemTime) -> Option<String> {
@@ -143,14 +369,6 @@ fn format_float(value: f64) -> String {
         .to_string()
 }
 
-fn escape_html<S: AsRef<str>>(input: S) -> String {
-    input
-        .as_ref()
-        .replace('&', "&amp;")
-        .replace('<', "&lt;")
-        .replace('>', "&gt;")
-}
-
 #[derive(Debug, Clone)]
 enum EntryType {
     Dir,
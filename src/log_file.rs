@@ -0,0 +1,132 @@
+//! The `--log-file` of the binary, which keeps the log lines and the requests in a file
+//! as well as on stderr, rotating it before it grows too large.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use live_server::{Change, RequestSummary};
+use log::{Log, Metadata, Record};
+
+/// The size at which the file is moved to `FILE.1`, and the older ones to `FILE.2` and so on.
+const MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+/// How many of the rotated files are kept.
+const MAX_FILES: usize = 5;
+
+#[derive(Clone)]
+pub(crate) struct LogFile {
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    pub(crate) fn open(path: PathBuf) -> io::Result<Self> {
+        let file = open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(LogFile {
+            inner: Arc::new(Mutex::new(Inner { path, file, size })),
+        })
+    }
+
+    /// Append the line with the time, e.g. `2024-05-01T12:00:00Z INFO  live_server] ...`.
+    pub(crate) fn write_line(&self, line: &str) {
+        let time = humantime::format_rfc3339_seconds(SystemTime::now());
+        let line = format!("{time} {line}\n");
+        let mut inner = self.inner.lock().unwrap();
+        if let Err(err) = inner.write(line.as_bytes()) {
+            // Not through the logger, which would write to the file again.
+            eprintln!("Failed to write to {:?}: {}", inner.path, err);
+        }
+    }
+
+    pub(crate) fn request(&self, request: &RequestSummary) {
+        let client = request.client.map(|ip| ip.to_string()).unwrap_or_default();
+        self.write_line(&format!(
+            "REQUEST {} {} {} {}ms {} {}",
+            request.method,
+            request.uri,
+            request.status,
+            request.duration.as_millis(),
+            client,
+            request.request_id
+        ));
+    }
+
+    pub(crate) fn reload(&self, changes: &[Change]) {
+        let paths: Vec<&str> = changes.iter().map(Change::path).collect();
+        self.write_line(format!("RELOAD {}", paths.join(" ")).trim_end());
+    }
+}
+
+impl Inner {
+    fn write(&mut self, buf: &[u8]) -> io::Result<()> {
+        if self.size > 0 && self.size + buf.len() as u64 > MAX_SIZE {
+            self.rotate()?;
+        }
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for n in (1..MAX_FILES).rev() {
+            let from = numbered(&self.path, n);
+            if from.exists() {
+                fs::rename(from, numbered(&self.path, n + 1))?;
+            }
+        }
+        fs::rename(&self.path, numbered(&self.path, 1))?;
+        self.file = open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(format!(".{n}"));
+    PathBuf::from(path)
+}
+
+/// The logger writing the records to the file too, after the logger of stderr.
+pub(crate) struct TeeLogger {
+    pub(crate) inner: env_logger::Logger,
+    pub(crate) file: LogFile,
+}
+
+impl Log for TeeLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.inner.matches(record) {
+            return;
+        }
+        self.inner.log(record);
+        self.file.write_line(&format!(
+            "{:<5} {}] {}",
+            record.level(),
+            record.target(),
+            record.args()
+        ));
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
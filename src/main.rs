@@ -1,48 +1,847 @@
+use std::{
+    error::Error,
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::LazyLock,
+    time::Duration,
+};
+
 use clap::Parser;
-use env_logger::Env;
-use live_server::listen;
+use env_logger::{Env, Target, WriteStyle};
+use live_server::{listen_with_options, DiskStorage, ListenError, Options, RequestSummary};
+use log_file::{LogFile, TeeLogger};
+use tokio_util::sync::CancellationToken;
+
+mod log_file;
+mod tui;
 
 /// Launch a local network server with live reload feature for static pages.
 #[derive(Parser)]
 #[clap(version)]
 struct Args {
-    /// Set the root path of the static assets
+    /// Set the root path of the static assets, a single file to serve at `/`, or a zip archive
     #[clap(default_value = ".")]
     root: String,
     /// Disable live reload
     #[clap(short, long)]
     no_watch: bool,
+    /// Serve the files exactly as they are on the disk, without injecting the live reload
+    /// script or anything else, and without watching them
+    #[clap(long)]
+    no_inject: bool,
+    /// Only log the errors
+    #[clap(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Log the requests and the watcher events, or with `-vv` everything down to the
+    /// dependencies' debug logs. `RUST_LOG` takes precedence when set
+    #[clap(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
     /// Set the listener host
-    #[clap(short = 'H', long, default_value = "0.0.0.0")]
+    #[clap(
+        short = 'H',
+        long,
+        default_value = "0.0.0.0",
+        default_value_if("dual_stack", "true", "::")
+    )]
     host: String,
+    /// Listen on all the IPv4 and IPv6 interfaces with one socket, i.e. on `[::]`
+    #[clap(long)]
+    dual_stack: bool,
     /// Set the listener port
     #[clap(short, long, default_value = "0")]
     port: u16,
     /// Open the page in browser automatically
     #[clap(short, long)]
     open: bool,
+    /// Ignore file changes matching the glob pattern (can be used multiple times)
+    #[clap(long, value_name = "GLOB")]
+    ignore: Vec<String>,
+    /// Ignore file changes according to `.gitignore` and `.ignore` files
+    #[clap(long)]
+    gitignore: bool,
+    /// Only reload when files with these extensions change, e.g. `html,css,js`
+    #[clap(long, value_name = "EXT", value_delimiter = ',')]
+    watch_ext: Vec<String>,
+    /// Wait for file changes to settle before reloading, e.g. `800ms`
+    #[clap(long, value_name = "DURATION", value_parser = humantime::parse_duration, default_value = "200ms")]
+    debounce: Duration,
+    /// When the root is a file, also reload when other files in its directory change
+    #[clap(long)]
+    watch_siblings: bool,
+    /// Only watch directories up to this depth below the root, `0` for the root only
+    #[clap(long, value_name = "DEPTH")]
+    watch_depth: Option<usize>,
+    /// Also watch the path outside of the root (can be used multiple times)
+    #[clap(long, value_name = "PATH")]
+    watch_path: Vec<PathBuf>,
+    /// Run the command when files change, and only reload if it succeeds. Exits with 6 if
+    /// its last run failed
+    #[clap(long, value_name = "COMMAND")]
+    exec: Option<String>,
+    /// Hold the reload until no file has changed for the duration, e.g. `1s`
+    #[clap(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    quiet_period: Option<Duration>,
+    /// Only reload when the marker file is touched
+    #[clap(long, value_name = "FILE")]
+    reload_marker: Option<PathBuf>,
+    /// Inject preload hints for the nested imports of module scripts and stylesheets
+    #[clap(long)]
+    preload_hints: bool,
+    /// Report the accessibility violations found by axe-core on the page
+    #[clap(long)]
+    a11y: bool,
+    /// Show a badge on the page telling whether live reload is connected
+    #[clap(long)]
+    status_badge: bool,
+    /// Print the errors thrown in the browser in the terminal
+    #[clap(long)]
+    client_log: bool,
+    /// Inject the HTML snippet into the pages (can be used multiple times)
+    #[clap(long, value_name = "HTML")]
+    inject: Vec<String>,
+    /// Inject the content of the file into the pages (can be used multiple times)
+    #[clap(long, value_name = "FILE")]
+    inject_file: Vec<PathBuf>,
+    /// Load the injected scripts from an external file, for pages with a Content-Security-Policy
+    #[clap(long)]
+    csp: bool,
+    /// Ping the WebSocket clients at the interval to keep them connected, `0s` to disable
+    #[clap(long, value_name = "DURATION", value_parser = humantime::parse_duration, default_value = "30s")]
+    ping_interval: Duration,
+    /// Keep up to this many reload messages for the clients which have not received them
+    #[clap(long, value_name = "COUNT", default_value_t = 16, value_parser = clap::value_parser!(u32).range(1..))]
+    channel_capacity: u32,
+    /// Connect the injected script to this WebSocket URL instead of the page's host
+    #[clap(long, value_name = "URL")]
+    ws_url: Option<String>,
+    /// Serve files with the extension as the MIME type, e.g. `gltf=model/gltf+json`
+    /// (can be used multiple times)
+    #[clap(long, value_name = "EXT=TYPE", value_parser = parse_mime_type)]
+    mime: Vec<(String, String)>,
+    /// Serve another path for the request paths matching the regex, e.g. `/v1/(.*)=/latest/$1`
+    #[clap(long, value_name = "PATTERN=PATH", value_parser = parse_rewrite)]
+    rewrite: Vec<(String, String)>,
+    /// Serve the site under a base path instead of `/`, e.g. `/repo/`
+    #[clap(long, value_name = "PATH")]
+    base: Option<String>,
+    /// Wait before responding, e.g. `300ms`, or only for the paths matching a glob, e.g. `api/**=2s`
+    #[clap(long, value_name = "[GLOB=]DURATION", value_parser = parse_delay)]
+    delay: Vec<(Option<String>, Duration)>,
+    /// Limit the rate responses are sent at, e.g. `1Mbps` or `500KB/s`
+    #[clap(long, value_name = "RATE", value_parser = parse_rate)]
+    throttle: Option<u64>,
+    /// Fail a share of requests on purpose with 500 or a timeout, e.g. `5%`
+    #[clap(long, value_name = "RATE", value_parser = parse_chaos)]
+    chaos: Option<f64>,
+    /// Replace `{{ env:PUBLIC_NAME }}` in pages and scripts with the `PUBLIC_` environment
+    /// variables
+    #[clap(long)]
+    env_vars: bool,
+    /// Expand server-side includes, e.g. `<!--#include file="header.html" -->`
+    #[clap(long)]
+    ssi: bool,
+    /// Find files ignoring the case of the request path, e.g. `/Logo.PNG` for `logo.png`
+    #[clap(long)]
+    case_insensitive: bool,
+    /// Serve symlinks pointing outside of the root, which are refused by default
+    #[clap(long)]
+    follow_symlinks: bool,
+    /// Serve a page for the status code instead of the built-in one, e.g. `404=errors/404.html`
+    #[clap(long, value_name = "STATUS=PATH", value_parser = parse_error_page)]
+    error_page: Vec<(u16, PathBuf)>,
+    /// Answer requests under a URL prefix with the JSON files in a directory, e.g. `/api=./mocks`
+    #[clap(long, value_name = "PREFIX=DIR", value_parser = parse_mount)]
+    mock: Vec<(String, PathBuf)>,
+    /// Serve another directory at a URL prefix, e.g. `/assets=../shared/assets`
+    #[clap(long, value_name = "PREFIX=DIR", value_parser = parse_mount)]
+    mount: Vec<(String, PathBuf)>,
+    /// Do not append `charset=utf-8` to the content type of text files
+    #[clap(long)]
+    no_charset: bool,
+    /// Serve `about.html` for `/about` when the literal path does not exist
+    #[clap(long)]
+    clean_urls: bool,
+    /// Do not redirect directory paths without a trailing slash, e.g. `/docs` to `/docs/`
+    #[clap(long)]
+    no_trailing_slash_redirect: bool,
+    /// Serve dotfiles and show them in the directory listing
+    #[clap(long, overrides_with = "hide_dotfiles")]
+    show_dotfiles: bool,
+    /// Hide dotfiles from the directory listing and refuse to serve them (default), e.g.
+    /// to undo a `--show-dotfiles` earlier on the command line
+    #[clap(long, overrides_with = "show_dotfiles")]
+    hide_dotfiles: bool,
+    /// Refuse requests for dotfiles with 403 (default when not listening on loopback)
+    #[clap(long, overrides_with = "allow_dotfiles")]
+    deny_dotfiles: bool,
+    /// Do not refuse requests for dotfiles with 403, even when not listening on loopback
+    #[clap(long, overrides_with = "deny_dotfiles")]
+    allow_dotfiles: bool,
+    /// Let others write files under the root with `PUT` or the form of the directory
+    /// listing, each up to the size, e.g. `1GB`
+    #[clap(long, value_name = "SIZE", value_parser = parse_size, num_args = 0..=1, default_missing_value = "100MB")]
+    upload: Option<u64>,
+    /// Send the `Server` header with the value, which is not sent by default
+    #[clap(long, value_name = "VALUE", value_parser = parse_header_value)]
+    server_header: Option<String>,
+    /// Set the language of the directory listing, e.g. `de`, instead of following the browser
+    #[clap(long, value_name = "LANG")]
+    lang: Option<String>,
+    /// Enable the admin API with the bearer token
+    #[clap(long, value_name = "TOKEN")]
+    admin_token: Option<String>,
+    /// Add buttons for renaming and deleting files to the directory listing, which ask for
+    /// the admin token
+    #[clap(long, requires = "admin_token")]
+    manage: bool,
+    /// Persist the state of the server in the directory
+    #[clap(long, value_name = "DIR")]
+    state_dir: Option<PathBuf>,
+    /// Write the shutdown summary to the file as JSON
+    #[clap(long, value_name = "FILE")]
+    report: Option<PathBuf>,
+    /// Print a colored line for each request, e.g. `GET /index.html 200 3ms 5f0e2c91d4a3b7e6`
+    #[clap(long)]
+    log_requests: bool,
+    /// Also write the log lines and the requests to the file, which is rotated at 10 MB,
+    /// keeping `FILE.1` to `FILE.5`
+    #[clap(long, value_name = "FILE")]
+    log_file: Option<PathBuf>,
+    /// Show the requests, the clients and the file changes in live panes, with the keys
+    /// `r` to reload, `o` to open the browser and `q` to quit
+    #[clap(long, conflicts_with = "log_requests")]
+    tui: bool,
+    /// Also serve over HTTP/3 on the UDP port of the same number, with a self-signed
+    /// certificate (experimental)
+    #[cfg(feature = "http3")]
+    #[clap(long)]
+    http3: bool,
+    /// Also serve the root over WebDAV at `/_live-server/webdav/`, read-only unless with
+    /// the `--admin-token` as the password, or `--upload`
+    #[cfg(feature = "webdav")]
+    #[clap(long)]
+    webdav: bool,
+    /// Export a span for each request over OTLP/HTTP, to `OTEL_EXPORTER_OTLP_ENDPOINT` or
+    /// `http://localhost:4318`
+    #[cfg(feature = "otel")]
+    #[clap(long)]
+    otel: bool,
+    /// Serve the files from the size on, e.g. `64MB`, from memory maps kept until they
+    /// change. A file truncated while being sent crashes the server
+    #[cfg(feature = "mmap")]
+    #[clap(long, value_name = "SIZE", value_parser = parse_size, num_args = 0..=1, default_missing_value = "16MB")]
+    mmap: Option<u64>,
 }
 
+/// Exit codes, so that scripts can tell the failures apart.
+const EXIT_FAILURE: u8 = 1;
+/// The same as clap for the invalid arguments.
+const EXIT_OPTIONS: u8 = 2;
+const EXIT_BIND: u8 = 3;
+const EXIT_ROOT: u8 = 4;
+const EXIT_WATCHER: u8 = 5;
+const EXIT_EXEC: u8 = 6;
+
 #[tokio::main]
-async fn main() {
-    let env = Env::new().default_filter_or("info");
-    env_logger::init_from_env(env);
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    let filter = match (args.quiet, args.verbose) {
+        (true, _) => "error",
+        (false, 0) => "info",
+        (false, 1) => "info,live_server=debug",
+        (false, _) => "debug,live_server=trace",
+    };
+    let mut logger = env_logger::Builder::from_env(Env::new().default_filter_or(filter));
+    let tui = args.tui.then(tui::Tui::default);
+    if let Some(tui) = &tui {
+        logger
+            .target(Target::Pipe(Box::new(tui.log_writer())))
+            .write_style(WriteStyle::Never)
+            .format_timestamp(None);
+    }
+    let log_file = match args.log_file.clone().map(LogFile::open).transpose() {
+        Ok(log_file) => log_file,
+        Err(err) => {
+            eprintln!("Failed to open the log file: {}", err);
+            return exit(args.report.as_deref(), EXIT_FAILURE);
+        }
+    };
+    let logger = logger.build();
+    log::set_max_level(logger.filter());
+    let _ = match &log_file {
+        Some(file) => log::set_boxed_logger(Box::new(TeeLogger {
+            inner: logger,
+            file: file.clone(),
+        })),
+        None => log::set_boxed_logger(Box::new(logger)),
+    };
 
     let Args {
+        quiet,
+        verbose: _,
         host,
+        dual_stack,
         port,
         root,
         open,
         no_watch,
-    } = Args::parse();
+        no_inject,
+        ignore,
+        gitignore,
+        watch_ext,
+        debounce,
+        watch_depth,
+        watch_siblings,
+        watch_path,
+        exec,
+        quiet_period,
+        reload_marker,
+        preload_hints,
+        a11y,
+        status_badge,
+        client_log,
+        inject,
+        inject_file,
+        csp,
+        ping_interval,
+        channel_capacity,
+        ws_url,
+        mime,
+        mount,
+        mock,
+        rewrite,
+        base,
+        error_page,
+        follow_symlinks,
+        case_insensitive,
+        ssi,
+        env_vars,
+        delay,
+        throttle,
+        chaos,
+        no_charset,
+        clean_urls,
+        no_trailing_slash_redirect,
+        show_dotfiles,
+        hide_dotfiles,
+        deny_dotfiles,
+        allow_dotfiles,
+        lang,
+        server_header,
+        upload,
+        admin_token,
+        manage,
+        state_dir,
+        report,
+        log_requests,
+        log_file: _,
+        tui: _,
+        #[cfg(feature = "http3")]
+        http3,
+        #[cfg(feature = "webdav")]
+        webdav,
+        #[cfg(feature = "otel")]
+        otel,
+        #[cfg(feature = "mmap")]
+        mmap,
+    } = args;
 
-    let addr = format!("{}:{}", host, port);
-    let listener = listen(addr, root, !no_watch).await.unwrap();
+    let addr = match host.contains(':') && !host.starts_with('[') {
+        true => format!("[{}]:{}", host, port),
+        false => format!("{}:{}", host, port),
+    };
+    let options = Options {
+        watch: !no_watch && !no_inject,
+        dual_stack,
+        ..Default::default()
+    };
+    let banner = Banner {
+        root: PathBuf::from(&root),
+        links: Vec::new(),
+        watch: !no_watch && !no_inject,
+        base: base.clone(),
+        mounts: mount.clone(),
+    };
+    let mut listener = match listen_with_options(addr, root, options).await {
+        Ok(listener) => listener,
+        Err(err) => return exit(report.as_deref(), exit_code(&err)),
+    };
+    for pattern in ignore {
+        listener = listener.ignore(pattern);
+    }
+    listener = listener
+        .gitignore(gitignore)
+        .watch_siblings(watch_siblings)
+        .pristine(no_inject);
+    for ext in watch_ext {
+        listener = listener.watch_extension(ext);
+    }
+    for path in watch_path {
+        listener = listener.watch_path(path);
+    }
+    if let Some(command) = exec {
+        listener = listener.exec(command);
+    }
+    if let Some(duration) = quiet_period {
+        listener = listener.quiet_period(duration);
+    }
+    if let Some(marker) = reload_marker {
+        listener = listener.reload_marker(marker);
+    }
+    if let Some(depth) = watch_depth {
+        listener = listener.watch_depth(depth);
+    }
+    listener = listener
+        .debounce(debounce)
+        .preload_hints(preload_hints)
+        .a11y(a11y)
+        .status_badge(status_badge)
+        .client_log(client_log)
+        .csp(csp)
+        .ping_interval(ping_interval)
+        .channel_capacity(channel_capacity as usize)
+        .charset(!no_charset)
+        .clean_urls(clean_urls)
+        .trailing_slash_redirect(!no_trailing_slash_redirect)
+        // clap keeps only the last of `--show-dotfiles` and `--hide-dotfiles`.
+        .show_dotfiles(show_dotfiles && !hide_dotfiles);
+    if deny_dotfiles || allow_dotfiles {
+        listener = listener.deny_dotfiles(deny_dotfiles);
+    }
+    for (ext, mime) in mime {
+        listener = listener.mime_type(ext, mime);
+    }
+    for (prefix, dir) in mount {
+        listener = listener.mount(prefix, dir);
+    }
+    for (prefix, dir) in mock {
+        listener = listener.mock(prefix, dir);
+    }
+    if let Some(base) = base {
+        listener = listener.base(base);
+    }
+    listener = listener
+        .follow_symlinks(follow_symlinks)
+        .case_insensitive(case_insensitive)
+        .ssi(ssi)
+        .env_vars(env_vars);
+    if let Some(rate) = chaos {
+        listener = listener.chaos(rate);
+    }
+    if let Some(rate) = throttle {
+        listener = listener.throttle(rate);
+    }
+    for (pattern, duration) in delay {
+        listener = match pattern {
+            Some(pattern) => listener.delay_for(pattern, duration),
+            None => listener.delay(duration),
+        };
+    }
+    for (status, path) in error_page {
+        listener = listener.error_page(status, path);
+    }
+    for (pattern, path) in rewrite {
+        listener = listener.rewrite(pattern, path);
+    }
+    if let Some(lang) = lang {
+        listener = listener.lang(lang);
+    }
+    if let Some(value) = server_header {
+        listener = listener.server_header(value);
+    }
+    if let Some(max_bytes) = upload {
+        listener = listener.upload(max_bytes);
+    }
+    #[cfg(feature = "http3")]
+    {
+        listener = listener.http3(http3);
+    }
+    #[cfg(feature = "webdav")]
+    {
+        listener = listener.webdav(webdav);
+    }
+    #[cfg(feature = "otel")]
+    {
+        listener = listener.otel(otel);
+    }
+    #[cfg(feature = "mmap")]
+    if let Some(min_size) = mmap {
+        listener = listener.mmap(min_size);
+    }
+    if let Some(url) = ws_url {
+        listener = listener.ws_url(url);
+    }
+    for snippet in inject {
+        listener = listener.inject(snippet);
+    }
+    for path in inject_file {
+        listener = listener.inject_file(path);
+    }
+    if let Some(token) = admin_token {
+        listener = listener.admin_token(token);
+    }
+    listener = listener.manage(manage);
+    if let Some(dir) = state_dir {
+        match DiskStorage::new(&dir) {
+            Ok(storage) => listener = listener.storage(storage),
+            Err(err) => {
+                log::error!("Failed to create the state directory {:?}: {}", dir, err);
+                return exit(report.as_deref(), EXIT_FAILURE);
+            }
+        }
+    }
+
+    if log_requests {
+        listener = listener.on_request(print_request);
+    }
+    if let Some(file) = log_file {
+        let requests = file.clone();
+        listener = listener
+            .on_request(move |request| requests.request(request))
+            .on_reload(move |changes| file.reload(changes));
+    }
+    let banner = Banner {
+        links: listener.links().unwrap_or_default(),
+        ..banner
+    };
 
     if open {
         let link = listener.link().unwrap();
         open::that(link).unwrap();
     }
 
-    listener.start().await.unwrap();
+    let quit = CancellationToken::new();
+    let stop = CancellationToken::new();
+    let ui = match tui {
+        Some(tui) => {
+            let requests = tui.clone();
+            let reloads = tui.clone();
+            listener = listener
+                .on_request(move |request| requests.request(request))
+                .on_reload(move |changes| reloads.reload(changes));
+            let header = tui::Header {
+                root: banner.root().display().to_string(),
+                links: banner.links,
+                watch: banner.watch,
+            };
+            match tui.spawn(header, quit.clone(), stop.clone()) {
+                Ok(ui) => Some(ui),
+                Err(err) => {
+                    log::error!("Failed to start the terminal UI: {}", err);
+                    return exit(report.as_deref(), EXIT_FAILURE);
+                }
+            }
+        }
+        None => {
+            if !quiet {
+                banner.print();
+            }
+            None
+        }
+    };
+
+    let signal = async move {
+        tokio::select! {
+            _ = shutdown_signal() => {}
+            _ = quit.cancelled() => {}
+        }
+    };
+    let result = listener.graceful_shutdown(signal).start().await;
+    stop.cancel();
+    if let Some(ui) = ui {
+        let _ = ui.join();
+    }
+
+    let code = match &result {
+        Ok(()) => 0,
+        Err(err) => {
+            log::error!("{}", err);
+            match err.downcast_ref::<ListenError>() {
+                Some(err) => exit_code(err),
+                None => EXIT_FAILURE,
+            }
+        }
+    };
+    exit(report.as_deref(), code)
+}
+
+/// Print the report, and write it if asked to, whether the server ran or failed to start.
+fn exit(report: Option<&Path>, code: u8) -> ExitCode {
+    print_report(report, code);
+    ExitCode::from(code)
+}
+
+/// Wait for Ctrl-C, or for the termination signal on Unix, e.g. from `kill` or Docker.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            },
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+fn parse_mime_type(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((ext, mime)) if !ext.is_empty() && mime.contains('/') => {
+            Ok((ext.to_string(), mime.to_string()))
+        }
+        _ => Err(format!(
+            "expected EXT=TYPE, e.g. gltf=model/gltf+json, got {value:?}"
+        )),
+    }
+}
+
+fn parse_delay(value: &str) -> Result<(Option<String>, Duration), String> {
+    let (pattern, duration) = match value.rsplit_once('=') {
+        Some((pattern, duration)) => {
+            globset::Glob::new(pattern).map_err(|err| err.to_string())?;
+            (Some(pattern.to_string()), duration)
+        }
+        None => (None, value),
+    };
+    let duration = humantime::parse_duration(duration).map_err(|err| err.to_string())?;
+    Ok((pattern, duration))
+}
+
+/// Parse a rate in bits per second, e.g. `1Mbps`, or bytes per second, e.g. `500KB/s`,
+/// into bytes per second.
+fn parse_rate(value: &str) -> Result<u64, String> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let bytes_per_unit = match unit.trim() {
+        "bps" => 1.0 / 8.0,
+        "Kbps" | "kbps" => 1e3 / 8.0,
+        "Mbps" => 1e6 / 8.0,
+        "Gbps" => 1e9 / 8.0,
+        "B/s" => 1.0,
+        "KB/s" | "kB/s" => 1e3,
+        "MB/s" => 1e6,
+        "GB/s" => 1e9,
+        _ => {
+            return Err(format!(
+                "expected a rate like 1Mbps or 500KB/s, got {value:?}"
+            ))
+        }
+    };
+    match number.parse::<f64>() {
+        Ok(number) if number > 0.0 => Ok(((number * bytes_per_unit) as u64).max(1)),
+        _ => Err(format!("expected a positive rate, got {value:?}")),
+    }
+}
+
+/// Parse a size in bytes, e.g. `1048576`, or with a unit, e.g. `500KB` or `1GB`.
+fn parse_size(value: &str) -> Result<u64, String> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let bytes_per_unit = match unit.trim() {
+        "" | "B" => 1.0,
+        "KB" | "kB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        _ => return Err(format!("expected a size like 500KB or 1GB, got {value:?}")),
+    };
+    match number.parse::<f64>() {
+        Ok(number) if number > 0.0 => Ok((number * bytes_per_unit) as u64),
+        _ => Err(format!("expected a positive size, got {value:?}")),
+    }
+}
+
+/// Parse a share of requests, either as a percentage, e.g. `5%`, or a fraction, e.g. `0.05`.
+fn parse_chaos(value: &str) -> Result<f64, String> {
+    let rate = match value.strip_suffix('%') {
+        Some(percent) => percent.parse::<f64>().map(|percent| percent / 100.0),
+        None => value.parse(),
+    };
+    match rate {
+        Ok(rate) if (0.0..=1.0).contains(&rate) => Ok(rate),
+        _ => Err(format!("expected a rate like 5% or 0.05, got {value:?}")),
+    }
+}
+
+fn parse_error_page(value: &str) -> Result<(u16, PathBuf), String> {
+    match value.split_once('=') {
+        Some((status, path)) if !path.is_empty() => match status.parse() {
+            Ok(status @ 400..=599) => Ok((status, PathBuf::from(path))),
+            _ => Err(format!("expected an error status code, got {status:?}")),
+        },
+        _ => Err(format!(
+            "expected STATUS=PATH, e.g. 404=errors/404.html, got {value:?}"
+        )),
+    }
+}
+
+fn parse_rewrite(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((pattern, path)) if !pattern.is_empty() && path.starts_with('/') => {
+            regex::Regex::new(pattern).map_err(|err| err.to_string())?;
+            Ok((pattern.to_string(), path.to_string()))
+        }
+        _ => Err(format!(
+            "expected PATTERN=PATH, e.g. /v1/(.*)=/latest/$1, got {value:?}"
+        )),
+    }
+}
+
+fn parse_mount(value: &str) -> Result<(String, PathBuf), String> {
+    match value.split_once('=') {
+        // The leading `/` is optional, but shown in the summary.
+        Some((prefix, dir)) if !prefix.is_empty() && !dir.is_empty() => {
+            let prefix = format!("/{}", prefix.trim_matches('/'));
+            Ok((prefix, PathBuf::from(dir)))
+        }
+        _ => Err(format!(
+            "expected PREFIX=DIR, e.g. /assets=../shared/assets or assets=../shared/assets, \
+            got {value:?}"
+        )),
+    }
+}
+
+fn parse_header_value(value: &str) -> Result<String, String> {
+    match axum::http::HeaderValue::from_str(value) {
+        Ok(_) => Ok(value.to_string()),
+        Err(_) => Err(format!("expected a valid header value, got {value:?}")),
+    }
+}
+
+fn exit_code(err: &ListenError) -> u8 {
+    match err {
+        ListenError::Bind(_) => EXIT_BIND,
+        ListenError::Root(_) => EXIT_ROOT,
+        ListenError::Watcher(_) => EXIT_WATCHER,
+        ListenError::Exec(_) => EXIT_EXEC,
+        ListenError::Options(_) => EXIT_OPTIONS,
+    }
+}
+
+/// Whether to color the terminal output, following <https://no-color.org>.
+static COLOR: LazyLock<bool> =
+    LazyLock::new(|| std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none());
+
+/// Wrap the text in the ANSI SGR code when coloring, e.g. `32` for green.
+fn paint(code: &str, text: impl std::fmt::Display) -> String {
+    match *COLOR {
+        true => format!("\x1b[{code}m{text}\x1b[0m"),
+        false => text.to_string(),
+    }
+}
+
+/// The settings shown in the startup block.
+struct Banner {
+    root: PathBuf,
+    links: Vec<String>,
+    watch: bool,
+    base: Option<String>,
+    mounts: Vec<(String, PathBuf)>,
+}
+
+impl Banner {
+    fn root(&self) -> PathBuf {
+        dunce::canonicalize(&self.root).unwrap_or_else(|_| self.root.clone())
+    }
+
+    fn print(&self) {
+        let root = self.root();
+        let mut lines = vec![
+            String::new(),
+            format!(
+                "  {} {}",
+                paint("1;32", "live-server"),
+                paint("2", concat!("v", env!("CARGO_PKG_VERSION")))
+            ),
+            String::new(),
+            format!("  {}  {}", paint("2", "Root "), root.display()),
+        ];
+        for (i, link) in self.links.iter().enumerate() {
+            let label = if i == 0 { "Local" } else { "     " };
+            lines.push(format!("  {}  {}", paint("2", label), paint("36", link)));
+        }
+        let watch = match self.watch {
+            true => paint("32", "on"),
+            false => paint("33", "off"),
+        };
+        lines.push(format!("  {}  {}", paint("2", "Watch"), watch));
+        if let Some(base) = &self.base {
+            lines.push(format!("  {}  {}", paint("2", "Base "), base));
+        }
+        for (prefix, dir) in &self.mounts {
+            lines.push(format!(
+                "  {}  {} -> {}",
+                paint("2", "Mount"),
+                prefix,
+                dir.display()
+            ));
+        }
+        eprintln!("{}\n", lines.join("\n"));
+    }
+}
+
+fn print_request(request: &RequestSummary) {
+    let status = match request.status {
+        200..=299 => "32",
+        300..=399 => "36",
+        400..=499 => "33",
+        _ => "31",
+    };
+    eprintln!(
+        "{} {} {} {} {}",
+        paint("1", &request.method),
+        request.uri,
+        paint(status, request.status),
+        paint("2", format!("{}ms", request.duration.as_millis())),
+        paint("2", &request.request_id)
+    );
+}
+
+fn print_report(path: Option<&Path>, exit_code: u8) {
+    let stats = live_server::stats();
+    log::info!(
+        "Served {} requests ({} bytes) in {:.1}s, {} reloads, {} errors",
+        stats.requests,
+        stats.bytes,
+        stats.uptime.as_secs_f64(),
+        stats.reloads,
+        stats.errors,
+    );
+    for client in stats.clients.iter().take(5) {
+        log::info!(
+            "  {}: {} requests ({} bytes)",
+            client.ip,
+            client.requests,
+            client.bytes
+        );
+    }
+
+    if let Some(path) = path {
+        if let Err(err) = write_report(path, stats, exit_code) {
+            log::error!("Failed to write report to {:?}: {}", path, err);
+        }
+    }
+}
+
+fn write_report(
+    path: &Path,
+    stats: live_server::Stats,
+    exit_code: u8,
+) -> Result<(), Box<dyn Error>> {
+    let mut report = serde_json::to_value(stats)?;
+    report["exit_code"] = exit_code.into();
+    std::fs::write(path, serde_json::to_string_pretty(&report)?)?;
+    Ok(())
 }
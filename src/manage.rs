@@ -0,0 +1,87 @@
+//! Renaming and deleting the files under the root from the directory listing, as enabled
+//! by [`Listener::manage`](crate::Listener::manage). The routes are part of the admin API,
+//! so they need its token.
+
+use std::path::PathBuf;
+
+use axum::{extract::Path, http::StatusCode, Json};
+use serde::Deserialize;
+
+use crate::server::resolve_mount;
+use crate::upload::{file_name, writable_path};
+use crate::OPTIONS;
+
+#[derive(Deserialize)]
+pub(crate) struct Rename {
+    name: String,
+}
+
+/// Delete the file, or the directory with everything in it.
+pub(crate) async fn delete_file(Path(path): Path<String>) -> StatusCode {
+    let path = match entry(&path) {
+        Ok(path) => path,
+        Err(status) => return status,
+    };
+    let result = match tokio::fs::symlink_metadata(&path).await {
+        Ok(metadata) if metadata.is_dir() => tokio::fs::remove_dir_all(&path).await,
+        Ok(_) => tokio::fs::remove_file(&path).await,
+        Err(_) => return StatusCode::NOT_FOUND,
+    };
+    match result {
+        Ok(()) => {
+            log::info!("[MANAGE] Deleted {:?}", path);
+            StatusCode::NO_CONTENT
+        }
+        Err(err) => {
+            log::error!("Failed to delete {:?}: {}", path, err);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Rename the file or the directory, keeping it in the same directory.
+pub(crate) async fn rename_file(
+    Path(path): Path<String>,
+    Json(rename): Json<Rename>,
+) -> StatusCode {
+    let path = match entry(&path) {
+        Ok(path) => path,
+        Err(status) => return status,
+    };
+    if tokio::fs::symlink_metadata(&path).await.is_err() {
+        return StatusCode::NOT_FOUND;
+    }
+    if rename.name.contains(['/', '\\']) {
+        return StatusCode::BAD_REQUEST;
+    }
+    let Some(name) = file_name(&rename.name) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    let target = path.with_file_name(name);
+    if tokio::fs::symlink_metadata(&target).await.is_ok() {
+        return StatusCode::CONFLICT;
+    }
+    match tokio::fs::rename(&path, &target).await {
+        Ok(()) => {
+            log::info!("[MANAGE] Renamed {:?} to {:?}", path, target);
+            StatusCode::NO_CONTENT
+        }
+        Err(err) => {
+            log::error!("Failed to rename {:?}: {}", path, err);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// The file or directory at the URL path, which cannot be the root or a mounted directory.
+fn entry(path: &str) -> Result<PathBuf, StatusCode> {
+    if !OPTIONS.get().unwrap().manage {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let path = format!("/{}", path.trim_end_matches('/'));
+    let (_, _, rest) = resolve_mount(&path);
+    if rest.trim_matches('/').is_empty() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    writable_path(&path)
+}
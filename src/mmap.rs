@@ -0,0 +1,94 @@
+//! Serving the large files from memory maps, as enabled by
+//! [`Listener::mmap`](crate::Listener::mmap), which are kept until the files change or
+//! other files are mapped.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+    time::{Instant, SystemTime},
+};
+
+use bytes::Bytes;
+use memmap2::Mmap;
+
+/// How many maps are kept, dropping the one used the longest ago, so that serving many
+/// large files does not keep them all mapped.
+const MAX_MAPS: usize = 64;
+
+/// The maps of the files served so far, by path.
+static MAPS: LazyLock<Mutex<HashMap<PathBuf, Mapped>>> = LazyLock::new(Default::default);
+
+struct Mapped {
+    modified: SystemTime,
+    len: u64,
+    bytes: Bytes,
+    used: Instant,
+}
+
+/// Drop the maps of the changed files, as seen by the watcher, so that one rewritten in
+/// place is mapped again even if its size and modification time look the same.
+pub(crate) fn forget<'a>(paths: impl IntoIterator<Item = &'a Path>) {
+    let mut maps = MAPS.lock().unwrap();
+    for path in paths {
+        maps.remove(path);
+    }
+}
+
+/// Drop all the maps, when the server stops.
+pub(crate) fn clear() {
+    MAPS.lock().unwrap().clear();
+}
+
+/// The content of the file if it has `min_size` bytes or more, mapped again only if it
+/// changed since the last request. `None` for the smaller files, or if the file cannot be
+/// mapped, which the caller reports when reading it.
+pub(crate) fn map(path: &Path, min_size: u64) -> Option<Bytes> {
+    let Ok(file) = File::open(path) else {
+        MAPS.lock().unwrap().remove(path);
+        return None;
+    };
+    let metadata = file.metadata().ok()?;
+    if !metadata.is_file() || metadata.len() < min_size {
+        return None;
+    }
+    let modified = metadata.modified().ok()?;
+
+    let mut maps = MAPS.lock().unwrap();
+    if let Some(mapped) = maps.get_mut(path) {
+        if mapped.modified == modified && mapped.len == metadata.len() {
+            mapped.used = Instant::now();
+            return Some(mapped.bytes.clone());
+        }
+    }
+    // SAFETY: the map is only read. A file truncated meanwhile raises `SIGBUS`, which
+    // `Listener::mmap` warns about.
+    let bytes = match unsafe { Mmap::map(&file) } {
+        Ok(mmap) => Bytes::from_owner(mmap),
+        Err(err) => {
+            log::warn!("Failed to map {:?}: {}", path, err);
+            maps.remove(path);
+            return None;
+        }
+    };
+    maps.insert(
+        path.to_path_buf(),
+        Mapped {
+            modified,
+            len: metadata.len(),
+            bytes: bytes.clone(),
+            used: Instant::now(),
+        },
+    );
+    if maps.len() > MAX_MAPS {
+        let oldest = maps
+            .iter()
+            .min_by_key(|(_, mapped)| mapped.used)
+            .map(|(path, _)| path.clone());
+        if let Some(oldest) = oldest {
+            maps.remove(&oldest);
+        }
+    }
+    Some(bytes)
+}
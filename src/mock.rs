@@ -0,0 +1,60 @@
+use std::path::{Component, PathBuf};
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::OPTIONS;
+
+/// Answer the requests under the prefixes set by [`Listener::mock`](crate::Listener::mock)
+/// with the JSON files in their directories, so that `POST /api/users` is answered with
+/// `users.POST.json`, or `users.json` if there is none.
+pub(crate) async fn mock_api(req: Request, next: Next) -> Response {
+    let Some(files) = find_mock(req.method(), req.uri().path()) else {
+        return next.run(req).await;
+    };
+
+    for file in files {
+        match tokio::fs::read(&file).await {
+            Ok(content) => {
+                log::debug!("[MOCK] {} {} -> {:?}", req.method(), req.uri().path(), file);
+                let content_type = HeaderValue::from_static("application/json");
+                return ([(header::CONTENT_TYPE, content_type)], content).into_response();
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                log::error!("Failed to read the mock {:?}: {}", file, err);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        }
+    }
+
+    log::warn!("[MOCK] No mock for {} {}", req.method(), req.uri().path());
+    StatusCode::NOT_FOUND.into_response()
+}
+
+/// The files which may answer the request, in order of preference, or `None` if the path
+/// is not under any of the mocked prefixes.
+fn find_mock(method: &Method, path: &str) -> Option<Vec<PathBuf>> {
+    let options = OPTIONS.get().unwrap();
+    let (dir, rest) = options.mocks.iter().find_map(|(prefix, dir)| {
+        let rest = path.strip_prefix(prefix.as_str())?;
+        rest.starts_with('/').then_some((dir, rest))
+    })?;
+
+    let name = rest.trim_matches('/');
+    let escapes = PathBuf::from(name)
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)));
+    if name.is_empty() || escapes {
+        return Some(vec![]);
+    }
+
+    Some(vec![
+        dir.join(format!("{name}.{method}.json")),
+        dir.join(format!("{name}.json")),
+    ])
+}
@@ -0,0 +1,122 @@
+//! Exporting a span for each request over OTLP/HTTP, as enabled by
+//! [`Listener::otel`](crate::Listener::otel), configured by the usual `OTEL_*` variables.
+
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::HeaderMap,
+    middleware::Next,
+    response::Response,
+};
+use opentelemetry::{
+    propagation::{Extractor, TextMapPropagator},
+    trace::{Span, SpanKind, Status, Tracer, TracerProvider},
+    KeyValue,
+};
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    trace::{SdkTracer, SdkTracerProvider},
+    Resource,
+};
+
+use crate::global::Global;
+use crate::server::is_internal;
+
+static TRACER: Global<SdkTracer> = Global::new();
+
+/// Flushes the spans left and stops exporting when the server stops.
+pub(crate) struct Exporting {
+    provider: SdkTracerProvider,
+}
+
+impl Drop for Exporting {
+    fn drop(&mut self) {
+        TRACER.clear();
+        if let Err(err) = self.provider.shutdown() {
+            log::warn!("Failed to export the last spans: {}", err);
+        }
+    }
+}
+
+/// Start exporting to `OTEL_EXPORTER_OTLP_ENDPOINT`, `http://localhost:4318` by default,
+/// as the `live-server` service unless `OTEL_SERVICE_NAME` is set.
+pub(crate) fn init() -> Result<Exporting, Box<dyn std::error::Error>> {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()?;
+    let mut resource = Resource::builder();
+    if std::env::var_os("OTEL_SERVICE_NAME").is_none() {
+        resource = resource.with_service_name("live-server");
+    }
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource.build())
+        .build();
+    TRACER.set(provider.tracer("live-server"));
+    Ok(Exporting { provider })
+}
+
+/// Record the request as a server span, which continues the trace of the `traceparent`
+/// header if the request has one, e.g. from a backend calling the mocks.
+pub(crate) async fn trace(req: Request, next: Next) -> Response {
+    let Some(tracer) = TRACER.get() else {
+        return next.run(req).await;
+    };
+    if is_internal(req.uri().path()) {
+        return next.run(req).await;
+    }
+
+    let parent = TraceContextPropagator::new().extract(&HeaderExtractor(req.headers()));
+    let method = req.method().to_string();
+    let mut attributes = vec![
+        KeyValue::new("http.request.method", method.clone()),
+        KeyValue::new("url.path", req.uri().path().to_string()),
+    ];
+    if let Some(query) = req.uri().query() {
+        attributes.push(KeyValue::new("url.query", query.to_string()));
+    }
+    if let Some(ConnectInfo(addr)) = req.extensions().get::<ConnectInfo<SocketAddr>>() {
+        attributes.push(KeyValue::new(
+            "client.address",
+            addr.ip().to_canonical().to_string(),
+        ));
+    }
+    let mut span = tracer
+        .span_builder(format!("{} {}", method, req.uri().path()))
+        .with_kind(SpanKind::Server)
+        .with_attributes(attributes)
+        .start_with_context(&*tracer, &parent);
+
+    let response = next.run(req).await;
+    let status = response.status();
+    span.set_attribute(KeyValue::new(
+        "http.response.status_code",
+        status.as_u16() as i64,
+    ));
+    if let Some(id) = response.headers().get("x-request-id") {
+        if let Ok(id) = id.to_str() {
+            span.set_attribute(KeyValue::new(
+                "http.request.header.x-request-id",
+                id.to_string(),
+            ));
+        }
+    }
+    if status.is_server_error() {
+        span.set_status(Status::error(status.to_string()));
+    }
+    span.end();
+    response
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use regex::Regex;
+
+use crate::path_to_string_but_readable;
+
+/// Stop following the dependency graph after this many files.
+const MAX_FILES: usize = 256;
+
+static SCRIPT_TAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?is)<script\b([^>]*)>"#).unwrap());
+static LINK_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(?is)<link\b([^>]*)>"#).unwrap());
+static ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?is)([a-z-]+)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap()
+});
+static JS_IMPORT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?m)(?:^|[;\s])(?:import|export)\s*(?:[\w*{}\s,$]*?\s*from\s*)?["']([^"']+)["']"#)
+        .unwrap()
+});
+static CSS_IMPORT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"@import\s+(?:url\(\s*)?["']?([^"')\s;]+)["']?"#).unwrap());
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Module,
+    Style,
+}
+
+/// Insert `<link rel="modulepreload">` and `<link rel="preload">` hints for the
+/// module scripts and stylesheets referenced by the page, including their nested
+/// imports, so that the browser can fetch the whole dependency graph in parallel.
+pub(crate) async fn inject_hints(root: &Path, html_path: &Path, html: String) -> String {
+    let base = html_path.parent().unwrap_or(root);
+
+    let mut queue = vec![];
+    for tag in SCRIPT_TAG.captures_iter(&html) {
+        let attrs = &tag[1];
+        if attribute(attrs, "type").as_deref() == Some("module") {
+            if let Some(src) = attribute(attrs, "src") {
+                queue.extend(resolve(root, base, &src).map(|path| (path, Kind::Module)));
+            }
+        }
+    }
+    for tag in LINK_TAG.captures_iter(&html) {
+        let attrs = &tag[1];
+        if attribute(attrs, "rel").is_some_and(|rel| rel.eq_ignore_ascii_case("stylesheet")) {
+            if let Some(href) = attribute(attrs, "href") {
+                queue.extend(resolve(root, base, &href).map(|path| (path, Kind::Style)));
+            }
+        }
+    }
+
+    // The files referenced by the page directly are discovered by the browser anyway,
+    // so only the nested dependencies need hints.
+    let direct: HashSet<PathBuf> = queue.iter().map(|(path, _)| path.clone()).collect();
+    let mut visited = direct.clone();
+    let mut hints = String::new();
+
+    while let Some((path, kind)) = queue.pop() {
+        if visited.len() > MAX_FILES {
+            break;
+        }
+        if !direct.contains(&path) {
+            let href = format!(
+                "/{}",
+                path_to_string_but_readable(path.strip_prefix(root).unwrap_or(&path))
+            );
+            hints.push_str(&match kind {
+                Kind::Module => format!(r#"<link rel="modulepreload" href="{href}">"#),
+                Kind::Style => format!(r#"<link rel="preload" as="style" href="{href}">"#),
+            });
+        }
+
+        let Ok(source) = tokio::fs::read_to_string(&path).await else {
+            continue;
+        };
+        let base = path.parent().unwrap_or(root);
+        let imports = match kind {
+            Kind::Module => &JS_IMPORT,
+            Kind::Style => &CSS_IMPORT,
+        };
+        for import in imports.captures_iter(&source) {
+            let specifier = &import[1];
+            // Bare module specifiers are resolved by import maps, not relative to the file.
+            if kind == Kind::Module && !specifier.starts_with(['.', '/']) {
+                continue;
+            }
+            if let Some(dependency) = resolve(root, base, specifier) {
+                if visited.insert(dependency.clone()) {
+                    queue.push((dependency, kind));
+                }
+            }
+        }
+    }
+
+    if hints.is_empty() {
+        return html;
+    }
+
+    match find_ignore_case(&html, "</head>") {
+        Some(index) => format!("{}{}{}", &html[..index], hints, &html[index..]),
+        None => html,
+    }
+}
+
+fn attribute(attrs: &str, name: &str) -> Option<String> {
+    ATTRIBUTE
+        .captures_iter(attrs)
+        .find(|attr| attr[1].eq_ignore_ascii_case(name))
+        .and_then(|attr| attr.get(2).or(attr.get(3)).or(attr.get(4)))
+        .map(|value| value.as_str().to_string())
+}
+
+/// Resolve a URL referenced by a file to an existing file under the root.
+fn resolve(root: &Path, base: &Path, url: &str) -> Option<PathBuf> {
+    let url = url.split(['?', '#']).next()?;
+    if url.contains("://") || url.starts_with("//") || url.starts_with("data:") {
+        return None;
+    }
+
+    let path = if let Some(url) = url.strip_prefix('/') {
+        root.join(url)
+    } else if !url.contains(':') {
+        base.join(url)
+    } else {
+        return None;
+    };
+
+    // Normalize the `.` and `..` components without touching the file system.
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    (normalized.starts_with(root) && normalized.is_file()).then_some(normalized)
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack.to_ascii_lowercase().find(needle)
+}
@@ -0,0 +1,156 @@
+use std::path::Path;
+
+use axum::{
+    body::Body,
+    extract::Query,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+};
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag};
+use serde::Deserialize;
+use tokio::io::AsyncReadExt;
+
+use crate::i18n;
+use crate::server::{internal_err, mime_override, query_path};
+use crate::util::{encode_path, escape_html};
+use crate::OPTIONS;
+
+/// Text files larger than this are truncated in the preview.
+const MAX_TEXT_SIZE: usize = 100 * 1024;
+
+/// How a file is shown in the preview pane of the directory listing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum PreviewKind {
+    Image,
+    Markdown,
+    Text,
+}
+
+impl PreviewKind {
+    pub(crate) fn of(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_string_lossy().to_lowercase();
+        if matches!(ext.as_str(), "md" | "markdown") {
+            return Some(PreviewKind::Markdown);
+        }
+        // HTML files are pages, so they are opened rather than previewed.
+        if matches!(ext.as_str(), "html" | "htm") {
+            return None;
+        }
+
+        let mime = mime_override(path).or_else(|| mime_guess::from_ext(&ext).first())?;
+        match (mime.type_().as_str(), mime.subtype().as_str()) {
+            ("image", _) => Some(PreviewKind::Image),
+            ("text", _) | ("application", "json" | "javascript" | "xml" | "toml") => {
+                Some(PreviewKind::Text)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct PreviewQuery {
+    /// The URL path of the file, e.g. `/images/logo.png`.
+    path: String,
+}
+
+/// Render the preview of a file as an HTML fragment.
+pub(crate) async fn preview(
+    Query(query): Query<PreviewQuery>,
+    headers: HeaderMap,
+) -> (StatusCode, HeaderMap, Body) {
+    let strings = i18n::negotiate(OPTIONS.get().unwrap().lang.as_deref(), &headers);
+    let Some(path) = query_path(&query.path) else {
+        return (StatusCode::NOT_FOUND, HeaderMap::new(), Body::empty());
+    };
+    let Some(kind) = PreviewKind::of(&path) else {
+        return (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            HeaderMap::new(),
+            Body::empty(),
+        );
+    };
+
+    let html = match kind {
+        PreviewKind::Image => format!(r#"<img src="{}" alt="" />"#, encode_path(&query.path)),
+        PreviewKind::Markdown | PreviewKind::Text => {
+            let mut text = match read_start(&path).await {
+                Ok(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    return (StatusCode::NOT_FOUND, HeaderMap::new(), Body::empty())
+                }
+                Err(err) => return internal_err(err),
+            };
+            let truncated = text.len() > MAX_TEXT_SIZE;
+            if truncated {
+                let mut end = MAX_TEXT_SIZE;
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+                text.truncate(end);
+            }
+
+            let mut html = match kind {
+                PreviewKind::Markdown => {
+                    let mut html = String::from(r#"<div class="markdown">"#);
+                    let parser = Parser::new_ext(&text, Options::all()).map(sanitize);
+                    html::push_html(&mut html, parser);
+                    html.push_str("</div>");
+                    html
+                }
+                _ => format!("<pre>{}</pre>", escape_html(text)),
+            };
+            if truncated {
+                html.push_str(&format!("<p><em>{}</em></p>", strings.truncated));
+            }
+            html
+        }
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.append(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+
+    (StatusCode::OK, headers, Body::from(html))
+}
+
+/// Read the start of the file, a few bytes past [`MAX_TEXT_SIZE`] so that the last
+/// character before the cut is complete, rather than the whole of a large log.
+async fn read_start(path: &Path) -> std::io::Result<Vec<u8>> {
+    let file = tokio::fs::File::open(path).await?;
+    let mut bytes = Vec::new();
+    file.take(MAX_TEXT_SIZE as u64 + 4)
+        .read_to_end(&mut bytes)
+        .await?;
+    Ok(bytes)
+}
+
+/// Show the raw HTML of the markdown as text, and drop the `javascript:` links and images, since the
+/// preview is inserted into the page of the listing.
+fn sanitize(event: Event) -> Event {
+    match event {
+        Event::Html(html) => Event::Text(html),
+        Event::Start(Tag::Link(kind, url, title)) => {
+            Event::Start(Tag::Link(kind, safe_url(url), title))
+        }
+        Event::Start(Tag::Image(kind, url, title)) => {
+            Event::Start(Tag::Image(kind, safe_url(url), title))
+        }
+        event => event,
+    }
+}
+
+fn safe_url(url: CowStr) -> CowStr {
+    // The browsers ignore the whitespace in the scheme, e.g. `java\tscript:`.
+    let scheme = url.split_once(':').map(|(scheme, _)| {
+        let scheme = scheme
+            .chars()
+            .filter(|c| !c.is_whitespace() && !c.is_control());
+        scheme.collect::<String>().to_lowercase()
+    });
+    match scheme.as_deref() {
+        Some("javascript" | "vbscript") => CowStr::Borrowed("#"),
+        _ => url,
+    }
+}
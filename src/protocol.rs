@@ -0,0 +1,45 @@
+use serde::Serialize;
+
+use crate::watcher::Change;
+
+/// The version of the messages sent through `/live-server-ws`. It is increased when
+/// a message changes in a way which is not backward compatible.
+pub(crate) const VERSION: u32 = 1;
+
+/// A message sent to the clients, serialized as JSON with a `type` and a `version`, e.g.
+/// `{"version":1,"type":"css-update","paths":["/index.css"]}`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub(crate) enum Message<'a> {
+    /// Reload the page.
+    Reload { changes: &'a [Change] },
+    /// Only stylesheets have changed, so they can be replaced without reloading the page.
+    CssUpdate { paths: Vec<&'a str> },
+    /// A build or watcher failure, shown as an overlay on the page.
+    #[cfg(feature = "watch")]
+    Error { message: &'a str },
+    /// The reply to a `{"type":"ping"}` sent by the client.
+    Ping,
+}
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    version: u32,
+    #[serde(flatten)]
+    message: &'a Message<'a>,
+}
+
+impl Message<'_> {
+    pub(crate) fn to_json(&self) -> String {
+        serde_json::to_string(&Envelope {
+            version: VERSION,
+            message: self,
+        })
+        .unwrap()
+    }
+}
+
+/// Whether the text sent by the client is a ping.
+pub(crate) fn is_ping(text: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(text).is_ok_and(|value| value["type"] == "ping")
+}
@@ -0,0 +1,18 @@
+// Apply the saved theme before the page is rendered, to avoid a flash of the other one.
+(() => {
+    const root = document.documentElement;
+    const saved = localStorage.getItem("live-server-theme");
+    if (saved) root.dataset.theme = saved;
+
+    document.addEventListener("DOMContentLoaded", () => {
+        const toggle = document.getElementById("theme-toggle");
+        if (!toggle) return;
+        toggle.addEventListener("click", () => {
+            const light = matchMedia("(prefers-color-scheme: light)").matches;
+            const current = root.dataset.theme ?? (light ? "light" : "dark");
+            const theme = current === "light" ? "dark" : "light";
+            root.dataset.theme = theme;
+            localStorage.setItem("live-server-theme", theme);
+        });
+    });
+})();
@@ -1,52 +1,381 @@
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
 use std::error::Error;
+use std::hash::{DefaultHasher, Hasher};
 use std::io::ErrorKind;
-use std::path::Path;
-use std::{fs, net::IpAddr};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use std::{
+    fs,
+    net::{IpAddr, SocketAddr},
+};
 
 use axum::{
-    body::Body,
-    extract::{ws::Message, Request, WebSocketUpgrade},
-    http::{header, HeaderMap, HeaderValue, StatusCode},
-    routing::get,
-    Router,
+    body::{Body, HttpBody},
+    extract::{
+        ws::{close_code, CloseFrame, Message, WebSocket},
+        DefaultBodyLimit, Query, Request, WebSocketUpgrade,
+    },
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, post, MethodRouter},
+    Router, ServiceExt,
+};
+use futures::{
+    sink::SinkExt,
+    stream::{SplitSink, StreamExt},
+    Stream,
 };
-use futures::{sink::SinkExt, stream::StreamExt};
-use local_ip_address::local_ip;
+use globset::GlobMatcher;
+use local_ip_address::list_afinet_netifas;
+use mime_guess::Mime;
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+use serde::Deserialize;
+use socket2::{Domain, Protocol, Socket, Type};
 use std::future::Future;
+use tokio::io::AsyncReadExt;
 use tokio::net::TcpListener;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tokio::time::Instant;
+use tokio_util::io::ReaderStream;
+use tokio_util::sync::CancellationToken;
+use tower::Layer;
 
-use crate::listing::serve_directory_listing;
-use crate::static_files::{
-    get_dir_link_svg, get_dir_svg, get_file_link_svg, get_file_svg, get_index_css, get_unknown_svg,
+use crate::a11y::{list_reports, receive_report};
+use crate::admin::admin_router;
+use crate::archive::read_from_archive;
+use crate::client_log::receive_error;
+use crate::env_vars;
+use crate::global::Global;
+use crate::hash::hash;
+use crate::health::health;
+use crate::i18n;
+use crate::mock::mock_api;
+use crate::protocol::{self, is_ping};
+use crate::ssi::process_includes;
+use crate::static_files::{get_error_html, get_index_css, get_theme_js};
+use crate::stats::{self, RequestHook};
+use crate::upload;
+use crate::util::{escape_html, has_hidden_component, render};
+use crate::virtual_files;
+use crate::watcher::ReloadHook;
+#[cfg(feature = "listing")]
+use crate::{
+    download::{archive, serve_archive, Format},
+    listing::{serve_directory_listing, ListingQuery},
+    preview::preview,
+    static_files::{
+        get_dir_link_svg, get_dir_svg, get_file_link_svg, get_file_svg, get_unknown_svg,
+    },
 };
-use crate::{ADDR, ROOT, TX, WATCH};
+use crate::{path_to_string_but_readable, preload, OPTIONS, ROOT, TX, WATCH};
+#[cfg(feature = "webdav")]
+use axum::routing::any;
+
+/// The options of the server, configured through the [`Listener`](crate::Listener).
+#[derive(Debug)]
+pub(crate) struct ServerOptions {
+    pub(crate) preload_hints: bool,
+    pub(crate) a11y: bool,
+    pub(crate) status_badge: bool,
+    pub(crate) client_log: bool,
+    pub(crate) snippets: Vec<Snippet>,
+    pub(crate) html_transforms: Vec<HtmlTransform>,
+    pub(crate) header_hooks: Vec<HeaderHook>,
+    pub(crate) server_header: Option<HeaderValue>,
+    /// The size limit of each uploaded file, if uploads are enabled.
+    pub(crate) upload_limit: Option<u64>,
+    pub(crate) manage: bool,
+    #[cfg(feature = "http3")]
+    pub(crate) http3: bool,
+    #[cfg(feature = "webdav")]
+    pub(crate) webdav: bool,
+    #[cfg(feature = "otel")]
+    pub(crate) otel: bool,
+    /// The size from which the files sent as they are get served from memory maps.
+    #[cfg(feature = "mmap")]
+    pub(crate) mmap: Option<u64>,
+    pub(crate) csp: bool,
+    /// Whether to serve the files exactly as they are, without injecting anything.
+    pub(crate) pristine: bool,
+    pub(crate) ping_interval: Duration,
+    /// How many messages are kept for the clients which have not received them yet.
+    pub(crate) channel_capacity: usize,
+    pub(crate) on_request: Vec<RequestHook>,
+    pub(crate) on_reload: Vec<ReloadHook>,
+    pub(crate) ws_url: Option<String>,
+    pub(crate) show_dotfiles: bool,
+    /// Refuse the requests for dotfiles with `403 Forbidden`, even if they are shown.
+    pub(crate) deny_dotfiles: bool,
+    pub(crate) lang: Option<String>,
+    pub(crate) clean_urls: bool,
+    pub(crate) trailing_slash_redirect: bool,
+    /// Extensions, lowercase and without the dot, mapped to their MIME types.
+    pub(crate) mime_types: HashMap<String, String>,
+    pub(crate) charset: bool,
+    /// URL prefixes, like `/assets` or an empty one for `/`, and the directories served
+    /// under them instead of the root.
+    pub(crate) mounts: Vec<(String, PathBuf)>,
+    /// The path the site is served under, like `/repo`, or empty to serve it at `/`.
+    pub(crate) base: String,
+    /// Status codes mapped to the pages served for them.
+    pub(crate) error_pages: HashMap<u16, PathBuf>,
+    /// Globs of the request paths, or `None` for all of them, and how long to wait before
+    /// responding to them. The first matching one is used.
+    pub(crate) delays: Vec<(Option<GlobMatcher>, Duration)>,
+    /// The rate to send the response bodies at, in bytes per second.
+    pub(crate) throttle: Option<u64>,
+    /// The share of the requests to fail on purpose, from 0 to 1.
+    pub(crate) chaos: f64,
+    /// Whether to substitute the `{{ env:PUBLIC_NAME }}` placeholders of the pages and scripts.
+    pub(crate) env_vars: bool,
+    /// Whether to expand the server-side includes of the pages.
+    pub(crate) ssi: bool,
+    /// Whether to find the files ignoring the case of their paths.
+    pub(crate) case_insensitive: bool,
+    /// Whether to serve the symlinks pointing outside of the root.
+    pub(crate) follow_symlinks: bool,
+    /// Patterns matching the whole request path, and the paths to serve instead.
+    pub(crate) rewrites: Vec<(Regex, String)>,
+    /// URL prefixes, like `/api`, and the directories of the JSON files answering them.
+    pub(crate) mocks: Vec<(String, PathBuf)>,
+    /// Whether to serve the files under the root, which is disabled for the bundles served
+    /// by [`listen_bundle`](crate::listen_bundle).
+    pub(crate) disk: bool,
+    /// The zip archive whose entries are served instead of the files under the root.
+    pub(crate) archive: Option<PathBuf>,
+    /// The file served at `/` when the root is a single file.
+    pub(crate) index_file: Option<PathBuf>,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        ServerOptions {
+            preload_hints: false,
+            a11y: false,
+            status_badge: false,
+            client_log: false,
+            snippets: Vec::new(),
+            html_transforms: Vec::new(),
+            header_hooks: Vec::new(),
+            server_header: None,
+            upload_limit: None,
+            manage: false,
+            #[cfg(feature = "http3")]
+            http3: false,
+            #[cfg(feature = "webdav")]
+            webdav: false,
+            #[cfg(feature = "otel")]
+            otel: false,
+            #[cfg(feature = "mmap")]
+            mmap: None,
+            csp: false,
+            ping_interval: Duration::from_secs(30),
+            channel_capacity: 16,
+            on_request: Vec::new(),
+            on_reload: Vec::new(),
+            ws_url: None,
+            show_dotfiles: false,
+            deny_dotfiles: false,
+            lang: None,
+            clean_urls: false,
+            trailing_slash_redirect: true,
+            mime_types: HashMap::new(),
+            charset: true,
+            mounts: Vec::new(),
+            base: String::new(),
+            error_pages: HashMap::new(),
+            delays: Vec::new(),
+            throttle: None,
+            chaos: 0.0,
+            env_vars: false,
+            pristine: false,
+            ssi: false,
+            case_insensitive: false,
+            follow_symlinks: false,
+            rewrites: Vec::new(),
+            mocks: Vec::new(),
+            disk: true,
+            archive: None,
+            index_file: None,
+        }
+    }
+}
+
+/// HTML injected into the served pages, after the built-in scripts.
+#[derive(Debug)]
+pub(crate) enum Snippet {
+    Inline(String),
+    /// Read on every request, so that editing the file takes effect on the next reload.
+    File(PathBuf),
+}
+
+impl Snippet {
+    async fn load(&self) -> Option<String> {
+        match self {
+            Snippet::Inline(html) => Some(html.clone()),
+            Snippet::File(path) => match tokio::fs::read_to_string(path).await {
+                Ok(html) => Some(html),
+                Err(err) => {
+                    log::warn!("Failed to read the snippet {:?}: {}", path, err);
+                    None
+                }
+            },
+        }
+    }
+}
+
+/// A rewrite of the served pages set by [`Listener::transform_html`](crate::Listener::transform_html),
+/// called with the request path and the HTML.
+#[derive(Clone)]
+pub(crate) struct HtmlTransform(pub(crate) Transform);
+
+type Transform = Arc<dyn Fn(&str, String) -> String + Send + Sync>;
+
+impl std::fmt::Debug for HtmlTransform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("HtmlTransform")
+    }
+}
+
+/// An adjustment of the response headers set by [`Listener::response_headers`](crate::Listener::response_headers),
+/// called with the request path.
+#[derive(Clone)]
+pub(crate) struct HeaderHook(pub(crate) HeaderCallback);
+
+type HeaderCallback = Arc<dyn Fn(&str, &mut HeaderMap) + Send + Sync>;
+
+impl std::fmt::Debug for HeaderHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("HeaderHook")
+    }
+}
+
+/// Cancelled when the server shuts down, to end the connections which would otherwise
+/// stay open, such as the WebSockets.
+pub(crate) static SHUTDOWN: Global<CancellationToken> = Global::new();
+
+pub(crate) fn shutdown() -> CancellationToken {
+    CancellationToken::clone(&SHUTDOWN.get().unwrap())
+}
 
 pub(crate) async fn serve(tcp_listener: TcpListener, router: Router) {
-    axum::serve(tcp_listener, router).await.unwrap();
+    // Strip the base path before routing, which a layer of the router would be too late for.
+    let app = middleware::from_fn(strip_base).layer(router);
+    let service = ServiceExt::<Request>::into_make_service_with_connect_info::<SocketAddr>(app);
+    axum::serve(tcp_listener, service)
+        .with_graceful_shutdown(shutdown().cancelled_owned())
+        .await
+        .unwrap();
 }
 
-pub(crate) async fn create_listener(addr: String) -> Result<TcpListener, String> {
-    match tokio::net::TcpListener::bind(&addr).await {
-        Ok(listener) => {
-            let port = listener.local_addr().unwrap().port();
-            let host = listener.local_addr().unwrap().ip();
-            let host = match host.is_unspecified() {
-                true => match local_ip() {
-                    Ok(addr) => addr,
-                    Err(err) => {
-                        log::warn!("Failed to get local IP address: {}", err);
-                        host
-                    }
-                },
-                false => host,
-            };
+/// Serve the site under the base path set by [`Listener::base`](crate::Listener::base)
+/// as if it was at `/`, and redirect `/` to the base path.
+pub(crate) async fn strip_base(mut req: Request, next: Next) -> Response {
+    let base = &OPTIONS.get().unwrap().base;
+    if base.is_empty() {
+        return next.run(req).await;
+    }
 
-            let addr = match host {
-                IpAddr::V4(host) => format!("{host}:{port}"),
-                IpAddr::V6(host) => format!("[{host}]:{port}"),
+    let path = req.uri().path();
+    let rest = match path.strip_prefix(base.as_str()) {
+        Some(rest) if rest.starts_with('/') => rest,
+        Some("") | None if path == base || path == "/" => {
+            let location = match req.uri().query() {
+                Some(query) => format!("{base}/?{query}"),
+                None => format!("{base}/"),
+            };
+            return match HeaderValue::from_str(&location) {
+                Ok(location) => (
+                    StatusCode::MOVED_PERMANENTLY,
+                    [(header::LOCATION, location)],
+                )
+                    .into_response(),
+                Err(_) => StatusCode::BAD_REQUEST.into_response(),
             };
-            log::info!("Listening on http://{addr}/");
-            ADDR.set(addr).unwrap();
+        }
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    };
+
+    let path_and_query = match req.uri().query() {
+        Some(query) => format!("{rest}?{query}"),
+        None => rest.to_string(),
+    };
+    let mut parts = req.uri().clone().into_parts();
+    parts.path_and_query = match path_and_query.parse() {
+        Ok(path_and_query) => Some(path_and_query),
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    match Uri::from_parts(parts) {
+        Ok(uri) => *req.uri_mut() = uri,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    }
+
+    next.run(req).await
+}
+
+/// The links the server can be reached at: the address it is bound to, or when bound to
+/// all the interfaces, localhost and the address of each interface, rather than a single
+/// one which may well belong to a VPN or a virtual adapter.
+pub(crate) fn links(addr: SocketAddr, base: &str) -> Vec<String> {
+    let port = addr.port();
+    let link = |host: IpAddr| match host {
+        IpAddr::V4(host) => format!("http://{host}:{port}{base}"),
+        IpAddr::V6(host) => format!("http://[{host}]:{port}{base}"),
+    };
+    if !addr.ip().is_unspecified() {
+        return vec![link(addr.ip())];
+    }
+
+    let mut links = vec![format!("http://localhost:{port}{base}")];
+    let interfaces = match list_afinet_netifas() {
+        Ok(interfaces) => interfaces,
+        Err(err) => {
+            log::warn!("Failed to list the network interfaces: {}", err);
+            return links;
+        }
+    };
+    for (_, ip) in interfaces {
+        // An IPv4 socket is not reachable over IPv6, and the link-local IPv6 addresses
+        // would need the zone of the interface.
+        let reachable = match ip {
+            IpAddr::V4(ip) => !ip.is_loopback(),
+            // `fe80::/10`, as `Ipv6Addr::is_unicast_link_local` needs a newer Rust.
+            IpAddr::V6(ip) => {
+                addr.is_ipv6() && !ip.is_loopback() && ip.segments()[0] & 0xffc0 != 0xfe80
+            }
+        };
+        let link = link(ip);
+        if reachable && !links.contains(&link) {
+            links.push(link);
+        }
+    }
+    links
+}
+
+pub(crate) async fn create_listener(addr: String, dual_stack: bool) -> Result<TcpListener, String> {
+    let ipv6 = match dual_stack {
+        true => tokio::net::lookup_host(&addr)
+            .await
+            .ok()
+            .and_then(|mut addrs| addrs.find(SocketAddr::is_ipv6)),
+        false => None,
+    };
+    let listener = match ipv6 {
+        Some(ipv6) => bind_dual_stack(ipv6),
+        None => tokio::net::TcpListener::bind(&addr).await,
+    };
+    match listener {
+        Ok(listener) => {
+            for link in links(listener.local_addr().unwrap(), "") {
+                log::info!("Listening on {link}/");
+            }
             Ok(listener)
         }
         Err(err) => {
@@ -61,10 +390,34 @@ pub(crate) async fn create_listener(addr: String) -> Result<TcpListener, String>
     }
 }
 
+/// Bind the IPv6 address with a socket accepting the IPv4 clients as well, as IPv4-mapped
+/// addresses, whatever the default of the system is.
+fn bind_dual_stack(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_only_v6(false)?;
+    #[cfg(not(windows))]
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// Serve the files, and write them if uploads are enabled, with their own size limit.
+/// The routes are made before the options are set, so the handlers of the uploads and of
+/// the other methods check whether they are enabled.
+fn static_route() -> MethodRouter {
+    get(static_assets)
+        .put(upload::put)
+        .post(upload::post)
+        .fallback(upload::method_not_allowed)
+        .layer(DefaultBodyLimit::disable())
+}
+
 pub(crate) fn create_server() -> Router {
-    Router::new()
-        .route("/", get(static_assets))
-        .route("/*path", get(static_assets))
+    let router = Router::new()
+        .route("/", static_route())
+        .route("/*path", static_route())
         .nest("/_live-server", static_router())
         .route(
             "/live-server-ws",
@@ -72,65 +425,659 @@ pub(crate) fn create_server() -> Router {
                 ws.on_failed_upgrade(|error| {
                     log::error!("Failed to upgrade websocket: {}", error);
                 })
-                .on_upgrade(|socket| async move {
-                    let (mut sender, mut receiver) = socket.split();
-                    let tx = TX.get().unwrap();
-                    let mut rx = tx.subscribe();
-                    let mut send_task = tokio::spawn(async move {
-                        while rx.recv().await.is_ok() {
-                            sender.send(Message::Text(String::new())).await.unwrap();
-                        }
-                    });
-                    let mut recv_task =
-                        tokio::spawn(
-                            async move { while let Some(Ok(_)) = receiver.next().await {} },
-                        );
-                    tokio::select! {
-                        _ = (&mut send_task) => recv_task.abort(),
-                        _ = (&mut recv_task) => send_task.abort(),
-                    };
-                })
+                .on_upgrade(handle_socket)
             }),
         )
+        .layer(middleware::from_fn(mock_api))
+        .layer(middleware::from_fn(not_modified))
+        .layer(middleware::from_fn(adjust_headers))
+        .layer(middleware::from_fn(chaos))
+        .layer(middleware::from_fn(throttle))
+        .layer(middleware::from_fn(delay))
+        .layer(middleware::from_fn(stats::track))
+        .layer(middleware::from_fn(server_header));
+    #[cfg(feature = "http3")]
+    let router = router.layer(middleware::from_fn(crate::http3::advertise));
+    #[cfg(feature = "otel")]
+    let router = router.layer(middleware::from_fn(crate::otel::trace));
+    router
 }
 
-async fn static_assets(req: Request<Body>) -> (StatusCode, HeaderMap, Body) {
-    let addr = ADDR.get().unwrap();
-    let root = ROOT.get().unwrap();
+/// Add the `Server` header set by [`Listener::server_header`](crate::Listener::server_header)
+/// to every response. There is none by default.
+async fn server_header(req: Request, next: Next) -> Response {
+    let mut response = next.run(req).await;
+    if let Some(value) = &OPTIONS.get().unwrap().server_header {
+        response.headers_mut().insert(header::SERVER, value.clone());
+    }
+    response
+}
+
+/// The largest body held in memory which is hashed for its `ETag`. The streamed files have
+/// one made of their size and modification time instead.
+const MAX_HASHED: u64 = 16 * 1024 * 1024;
+
+/// Answer `304 Not Modified` to the conditional requests for the files and the mocks
+/// which did not change since, as told by their `ETag`. The pages are hashed after the
+/// scripts are injected, so that they are sent again if an included file changed.
+async fn not_modified(req: Request, next: Next) -> Response {
+    let cacheable =
+        matches!(req.method().as_str(), "GET" | "HEAD") && !is_internal(req.uri().path());
+    let if_none_match = req.headers().get(header::IF_NONE_MATCH).cloned();
+    let response = next.run(req).await;
+    if !cacheable || response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let (etag, body) = match parts.headers.get(header::ETAG) {
+        Some(etag) => (etag.clone(), body),
+        None => match body.size_hint().exact() {
+            Some(len) if len <= MAX_HASHED => {
+                let bytes = match axum::body::to_bytes(body, len as usize).await {
+                    Ok(bytes) => bytes,
+                    Err(err) => return internal_err(err).into_response(),
+                };
+                let mut hasher = DefaultHasher::new();
+                hasher.write(&bytes);
+                let etag = HeaderValue::from_str(&format!("\"{:016x}\"", hasher.finish()));
+                (etag.unwrap(), Body::from(bytes))
+            }
+            _ => return Response::from_parts(parts, body),
+        },
+    };
+    parts.headers.insert(header::ETAG, etag.clone());
+
+    let matches = if_none_match
+        .as_ref()
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            // The weak comparison, since the validators only tell the content apart.
+            let weak = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+            let etag = weak(etag.to_str().unwrap_or_default());
+            value
+                .split(',')
+                .any(|tag| tag.trim() == "*" || weak(tag) == etag)
+        });
+    if !matches {
+        return Response::from_parts(parts, body);
+    }
+    parts.status = StatusCode::NOT_MODIFIED;
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::empty())
+}
+
+/// A weak `ETag` of the file made of its size and modification time, for the files which
+/// are not read into memory.
+fn file_etag(metadata: &std::fs::Metadata) -> Option<HeaderValue> {
+    let modified = metadata.modified().ok()?;
+    let nanos = modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_nanos();
+    HeaderValue::from_str(&format!("W/\"{:x}-{:x}\"", metadata.len(), nanos)).ok()
+}
+
+/// Let the callbacks of [`Listener::response_headers`](crate::Listener::response_headers)
+/// adjust the headers of the response. The live-server routes are left as they are.
+async fn adjust_headers(req: Request, next: Next) -> Response {
+    let hooks = &OPTIONS.get().unwrap().header_hooks;
+    let path = req.uri().path().to_string();
+    if hooks.is_empty() || is_internal(&path) {
+        return next.run(req).await;
+    }
+
+    let mut response = next.run(req).await;
+    for HeaderHook(hook) in hooks {
+        hook(&path, response.headers_mut());
+    }
+    response
+}
+
+/// Send the response body at the rate set by [`Listener::throttle`](crate::Listener::throttle),
+/// to see how images and videos load on a slow network. The live-server routes are not throttled.
+async fn throttle(req: Request, next: Next) -> Response {
+    let rate = match OPTIONS.get().unwrap().throttle {
+        Some(rate) if !is_internal(req.uri().path()) => rate,
+        _ => return next.run(req).await,
+    };
+
+    // Keep the length, so that the browser can show the progress.
+    let mut response = next.run(req).await;
+    if let Some(length) = response.body().size_hint().exact() {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_LENGTH, HeaderValue::from(length));
+    }
+
+    // Send small pieces often rather than large ones rarely, so the rate looks steady.
+    let piece_size = (rate / 20).max(1) as usize;
+    response.map(|body| {
+        let pieces = body.into_data_stream().flat_map(move |chunk| {
+            let pieces: Vec<_> = match chunk {
+                Ok(chunk) => (0..chunk.len())
+                    .step_by(piece_size)
+                    .map(|start| Ok(chunk.slice(start..chunk.len().min(start + piece_size))))
+                    .collect(),
+                Err(err) => vec![Err(err)],
+            };
+            futures::stream::iter(pieces)
+        });
+        Body::from_stream(pieces.then(move |piece| async move {
+            if let Ok(piece) = &piece {
+                let seconds = piece.len() as f64 / rate as f64;
+                tokio::time::sleep(Duration::from_secs_f64(seconds)).await;
+            }
+            piece
+        }))
+    })
+}
+
+/// How long a request timing out in the chaos mode hangs before the server gives up.
+const CHAOS_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Fail the share of the requests set by [`Listener::chaos`](crate::Listener::chaos), half
+/// of them with `500 Internal Server Error` and half by timing out, to test how the pages
+/// handle a misbehaving server. The live-server routes never fail.
+async fn chaos(req: Request, next: Next) -> Response {
+    let failing = fastrand::f64() < OPTIONS.get().unwrap().chaos;
+    if !failing || is_internal(req.uri().path()) {
+        return next.run(req).await;
+    }
+
+    if fastrand::bool() {
+        log::info!("[CHAOS] Failing {}", req.uri().path());
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    } else {
+        log::info!("[CHAOS] Timing out {}", req.uri().path());
+        tokio::select! {
+            _ = tokio::time::sleep(CHAOS_TIMEOUT) => {}
+            _ = shutdown().cancelled_owned() => {}
+        }
+        StatusCode::GATEWAY_TIMEOUT.into_response()
+    }
+}
+
+/// Whether the path is one of the routes of live-server, rather than of the site.
+pub(crate) fn is_internal(path: &str) -> bool {
+    path.starts_with("/_live-server/") || path == "/live-server-ws"
+}
+
+/// Wait before responding as set by [`Listener::delay`](crate::Listener::delay), to
+/// simulate a slow network or backend. The live-server routes are not delayed.
+async fn delay(req: Request, next: Next) -> Response {
+    let path = req.uri().path();
+    let internal = is_internal(path);
+    let options = OPTIONS.get().unwrap();
+    let delay = options.delays.iter().find(|(glob, _)| {
+        glob.as_ref()
+            .is_none_or(|glob| glob.is_match(path.trim_start_matches('/')))
+    });
+    if let Some((_, duration)) = delay.filter(|_| !internal) {
+        tokio::time::sleep(*duration).await;
+    }
+    next.run(req).await
+}
+
+async fn handle_socket(socket: WebSocket) {
+    let (mut sender, mut receiver) = socket.split();
+    let mut rx = TX.get().unwrap().subscribe();
+
+    // Keep idle connections alive through NATs and proxies, and drop the
+    // clients which do not answer.
+    let ping_interval = OPTIONS.get().unwrap().ping_interval;
+    let mut ping = (!ping_interval.is_zero())
+        .then(|| tokio::time::interval_at(Instant::now() + ping_interval, ping_interval));
+    let mut awaiting_pong = false;
+
+    loop {
+        tokio::select! {
+            _ = shutdown().cancelled_owned() => {
+                let frame = CloseFrame {
+                    code: close_code::AWAY,
+                    reason: "Server is shutting down".into(),
+                };
+                let _ = sender.send(Message::Close(Some(frame))).await;
+                break;
+            }
+            message = next_message(&mut rx) => {
+                let Some(message) = message else { break };
+                if !send(&mut sender, Message::Text(message)).await {
+                    break;
+                }
+            }
+            _ = async { ping.as_mut().unwrap().tick().await }, if ping.is_some() => {
+                if awaiting_pong {
+                    log::debug!("WebSocket client did not answer the ping, closing");
+                    break;
+                }
+                awaiting_pong = true;
+                if !send(&mut sender, Message::Ping(vec![])).await {
+                    break;
+                }
+            }
+            message = receiver.next() => match message {
+                Some(Ok(Message::Text(text))) if is_ping(&text) => {
+                    awaiting_pong = false;
+                    let pong = protocol::Message::Ping.to_json();
+                    if !send(&mut sender, Message::Text(pong)).await {
+                        break;
+                    }
+                }
+                Some(Ok(_)) => awaiting_pong = false,
+                _ => break,
+            },
+        }
+    }
+}
+
+/// Send the message to the WebSocket client, returning whether it is still connected.
+///
+/// The tab may be closed at any moment, e.g. in the middle of a reload, in which case
+/// its connection ends quietly, dropping its receiver so that the others are unaffected.
+async fn send(sender: &mut SplitSink<WebSocket, Message>, message: Message) -> bool {
+    match sender.send(message).await {
+        Ok(()) => true,
+        Err(err) => {
+            log::debug!("WebSocket client disconnected: {}", err);
+            false
+        }
+    }
+}
+
+/// Receive the next message for a client, or `None` once the server stops.
+///
+/// A client too slow to keep up, e.g. a throttled background tab, misses the messages
+/// which no longer fit in the channel, and gets a single reload in their place.
+async fn next_message(rx: &mut Receiver<String>) -> Option<String> {
+    match rx.recv().await {
+        Ok(message) => Some(message),
+        Err(RecvError::Lagged(missed)) => {
+            log::debug!("Client missed {} messages, reloading it", missed);
+            Some(protocol::Message::Reload { changes: &[] }.to_json())
+        }
+        Err(RecvError::Closed) => None,
+    }
+}
+
+/// Send the same messages as `/live-server-ws` as Server-Sent Events, for the
+/// networks where WebSocket upgrades are blocked, e.g. by a proxy.
+async fn events() -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let rx = TX.get().unwrap().subscribe();
+    let stream = futures::stream::unfold(rx, |mut rx| async move {
+        let message = next_message(&mut rx).await?;
+        Some((Ok(Event::default().data(message)), rx))
+    });
+
+    Sse::new(stream.take_until(shutdown().cancelled_owned())).keep_alive(KeepAlive::default())
+}
+
+/// How long a long-poll request waits for a message before returning empty.
+const POLL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many of the last messages are kept for the long-polling clients.
+const HISTORY_SIZE: usize = 16;
+
+/// The last messages sent to the clients, numbered, so that the long-polling clients get
+/// those sent between two of their requests.
+static HISTORY: Mutex<History> = Mutex::new(History {
+    last: 0,
+    messages: VecDeque::new(),
+});
+
+struct History {
+    /// The number of the last message, from 1.
+    last: u64,
+    messages: VecDeque<(u64, String)>,
+}
+
+impl History {
+    /// The first message after the one numbered `since`, or a reload if it is no longer
+    /// kept, along with its number.
+    fn after(&self, since: u64) -> Option<(u64, String)> {
+        if since >= self.last {
+            return None;
+        }
+        match self.messages.iter().find(|(number, _)| *number > since) {
+            Some((number, message)) if *number == since + 1 => Some((*number, message.clone())),
+            _ => Some((
+                self.last,
+                protocol::Message::Reload { changes: &[] }.to_json(),
+            )),
+        }
+    }
+}
+
+/// Send the message to all the clients, whether connected by WebSocket, Server-Sent
+/// Events or long polling.
+pub(crate) fn send_message(message: String) {
+    let mut history = HISTORY.lock().unwrap();
+    history.last += 1;
+    let number = history.last;
+    history.messages.push_back((number, message.clone()));
+    if history.messages.len() > HISTORY_SIZE {
+        history.messages.pop_front();
+    }
+    // Still holding the lock, so that the pollers woken up find the message.
+    let _ = TX.get().unwrap().send(message);
+}
+
+#[derive(Debug, Deserialize)]
+struct PollQuery {
+    /// The number of the last message the client got, from the `X-Live-Server-Cursor`
+    /// header of the previous response.
+    since: Option<u64>,
+}
+
+/// Wait for the next message of `/live-server-ws` and return it, or `204 No Content`
+/// if there is none within [`POLL_TIMEOUT`], for the browsers where neither
+/// WebSockets nor Server-Sent Events work.
+///
+/// The number of the message is sent in the `X-Live-Server-Cursor` header, for the next
+/// request to get the messages sent in the meantime with `?since=`. The first request,
+/// without it, returns right away with the number to start from.
+async fn poll(Query(query): Query<PollQuery>) -> (StatusCode, HeaderMap, Body) {
+    let mut headers = HeaderMap::new();
+    headers.append(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+
+    // Subscribe first, so that no message is sent between looking and waiting.
+    let mut rx = TX.get().unwrap().subscribe();
+    let last = HISTORY.lock().unwrap().last;
+    let since = query.since.unwrap_or(last);
+    let waiting = async {
+        loop {
+            if let Some(message) = HISTORY.lock().unwrap().after(since) {
+                return Some(message);
+            }
+            // The first request only gets the number to start from.
+            query.since?;
+            tokio::select! {
+                result = rx.recv() => if let Err(RecvError::Closed) = result {
+                    return None;
+                },
+                _ = shutdown().cancelled_owned() => return None,
+            }
+        }
+    };
+
+    match tokio::time::timeout(POLL_TIMEOUT, waiting).await {
+        Ok(Some((number, message))) => {
+            headers.append(CURSOR, HeaderValue::from(number));
+            headers.append(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            );
+            (StatusCode::OK, headers, Body::from(message))
+        }
+        _ => {
+            headers.append(CURSOR, HeaderValue::from(since));
+            (StatusCode::NO_CONTENT, headers, Body::empty())
+        }
+    }
+}
+
+const CURSOR: &str = "x-live-server-cursor";
+
+/// Render the script which connects to the WebSocket and reloads the page.
+fn client_js() -> String {
+    let options = OPTIONS.get().unwrap();
+    let js = render(
+        include_str!("templates/client.js").to_string(),
+        "ws_url",
+        serde_json::to_string(&options.ws_url).unwrap(),
+    );
+    render(js, "base", &options.base)
+}
+
+/// The inline `<script>` which connects to the WebSocket and reloads the page.
+pub(crate) fn reload_script() -> String {
+    script_tag(&client_js())
+}
+
+fn script_tag(js: &str) -> String {
+    format!("<script>\n{js}</script>\n")
+}
+
+/// The built-in scripts injected into the pages, according to the options.
+fn client_scripts() -> Vec<String> {
+    let options = OPTIONS.get().unwrap();
+    let mut scripts = vec![];
+    if *WATCH.get().unwrap() {
+        scripts.push(client_js());
+        if options.status_badge {
+            scripts.push(include_str!("templates/status.js").to_string());
+        }
+    }
+    if options.a11y {
+        let js = include_str!("templates/a11y.js").to_string();
+        scripts.push(render(js, "base", &options.base));
+    }
+    if options.client_log {
+        let js = include_str!("templates/client-log.js").to_string();
+        scripts.push(render(js, "base", &options.base));
+    }
+    scripts
+}
+
+/// Serve the built-in scripts as a single file, for the pages whose
+/// Content-Security-Policy blocks inline scripts.
+async fn client_bundle() -> (StatusCode, HeaderMap, Body) {
+    let mut headers = HeaderMap::new();
+    headers.append(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/javascript; charset=utf-8"),
+    );
+    headers.append(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+
+    (
+        StatusCode::OK,
+        headers,
+        Body::from(client_scripts().join("\n")),
+    )
+}
 
+static CSP_NONCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"'nonce-([A-Za-z0-9+/=_-]+)'").unwrap());
+
+/// Find the nonce allowed by the Content-Security-Policy `<meta>` tag of the page.
+fn csp_nonce(html: &str) -> Option<&str> {
+    CSP_NONCE
+        .captures(html)
+        .and_then(|captures| captures.get(1))
+        .map(|nonce| nonce.as_str())
+}
+
+/// Find the directory serving the URL path, which is the mounted directory with the
+/// longest matching prefix or else the root, along with the prefix and the rest of the path.
+pub(crate) fn resolve_mount(path: &str) -> (String, PathBuf, &str) {
+    let options = OPTIONS.get().unwrap();
+    options
+        .mounts
+        .iter()
+        .filter_map(|(prefix, dir)| {
+            let rest = path.strip_prefix(prefix.as_str())?;
+            (rest.is_empty() || rest.starts_with('/')).then_some((prefix, dir, rest))
+        })
+        .max_by_key(|(prefix, _, _)| prefix.len())
+        .map(|(prefix, dir, rest)| (prefix.clone(), dir.clone(), rest))
+        .unwrap_or_else(|| (String::new(), PathBuf::clone(&ROOT.get().unwrap()), path))
+}
+
+async fn static_assets(req: Request<Body>) -> (StatusCode, HeaderMap, Body) {
     // Get the path and mime of the static file.
-    let mut path = req.uri().path().to_string();
-    path.remove(0);
+    let path = match percent_decode_str(req.uri().path()).decode_utf8() {
+        Ok(path) => path,
+        Err(err) => {
+            log::warn!("Failed to decode the path {:?}: {}", req.uri().path(), err);
+            return (StatusCode::BAD_REQUEST, HeaderMap::new(), Body::empty());
+        }
+    };
 
-    let path = root.join(path);
+    let options = OPTIONS.get().unwrap();
+    let request_path = path.to_string();
+    // Rewrite the path before resolving it, as the CDN in production would.
+    let path = match options
+        .rewrites
+        .iter()
+        .find(|(regex, _)| regex.is_match(&path))
+    {
+        Some((regex, replacement)) => {
+            let rewritten = regex.replace(&path, replacement.as_str()).into_owned();
+            log::debug!("Rewrote {:?} to {:?}", path, rewritten);
+            rewritten.into()
+        }
+        None => path,
+    };
 
-    if !path.starts_with(root) {
-        return internal_err(std::io::Error::new(
-            ErrorKind::PermissionDenied,
-            "Path is outside of root directory",
-        ));
+    let read_virtual = |path: &str| {
+        virtual_files::get(path).or_else(|| {
+            let archive = options.archive.as_ref()?;
+            read_from_archive(archive, path).unwrap_or_else(|err| {
+                log::warn!("Failed to read {:?} from the archive: {}", path, err);
+                None
+            })
+        })
+    };
+
+    // Virtual directories have no listing, so serve their index right away.
+    let virtual_index = format!("{path}index.html");
+    let (virtual_file, path) = match read_virtual(&path) {
+        None if path.ends_with('/') => match read_virtual(&virtual_index) {
+            Some(content) => (Some(content), virtual_index.as_str()),
+            None => (None, &*path),
+        },
+        content => (content, &*path),
+    };
+
+    let (prefix, root, path) = resolve_mount(path);
+    let root = root.as_path();
+    if virtual_file.is_none() && !options.disk && root == *ROOT.get().unwrap() {
+        return not_found().await;
+    }
+
+    if virtual_file.is_none() && !root.exists() {
+        return waiting_for_root(root, req.headers()).await;
+    }
+
+    let path = root.join(path.trim_start_matches('/'));
+    let path = match options.case_insensitive && virtual_file.is_none() && !path.exists() {
+        true => find_ignoring_case(root, &path).unwrap_or(path),
+        false => path,
+    };
+
+    if !path.starts_with(root) || (virtual_file.is_none() && escapes_root(root, &path)) {
+        log::warn!("Refused to serve {:?} outside of the root", path);
+        return (StatusCode::FORBIDDEN, HeaderMap::new(), Body::empty());
+    }
+
+    if virtual_file.is_none() && has_hidden_component(root, &path) {
+        if options.deny_dotfiles {
+            log::warn!("Refused to serve dotfile {:?}", path);
+            return (StatusCode::FORBIDDEN, HeaderMap::new(), Body::empty());
+        }
+        if !options.show_dotfiles {
+            log::debug!("Refused to serve hidden file {:?}", path);
+            return not_found().await;
+        }
     }
 
-    let path = if path.is_dir() {
+    let path = match &options.index_file {
+        Some(file) if prefix.is_empty() && path == root => file.clone(),
+        _ => path,
+    };
+
+    let path = if virtual_file.is_some() {
+        path
+    } else if path.is_dir() {
+        // Relative links in the index resolve against the directory only with the slash.
+        if options.trailing_slash_redirect && !req.uri().path().ends_with('/') {
+            // A single leading slash, since `//docs/` would be the host `docs`.
+            let path = format!("/{}", req.uri().path().trim_start_matches('/'));
+            let location = match req.uri().query() {
+                Some(query) => format!("{}{}/?{}", options.base, path, query),
+                None => format!("{}{}/", options.base, path),
+            };
+            let mut headers = HeaderMap::new();
+            if let Ok(location) = HeaderValue::from_str(&location) {
+                headers.append(header::LOCATION, location);
+                return (StatusCode::MOVED_PERMANENTLY, headers, Body::empty());
+            }
+        }
+
+        #[cfg(feature = "listing")]
+        let Query(query) = Query::<ListingQuery>::try_from_uri(req.uri()).unwrap_or_default();
+        #[cfg(feature = "listing")]
+        if query.wants_zip() {
+            let show_dotfiles = options.show_dotfiles && !options.deny_dotfiles;
+            return serve_archive(path, show_dotfiles, Format::Zip);
+        }
+
         let index = path.join("index.html");
         if tokio::fs::try_exists(&index).await.unwrap_or(false) {
             index
         } else {
-            return serve_directory_listing(root, path).await;
+            // Without the listings, the directories are only served by their index.
+            #[cfg(not(feature = "listing"))]
+            return not_found().await;
+            #[cfg(feature = "listing")]
+            {
+                let strings = i18n::negotiate(options.lang.as_deref(), req.headers());
+                let prefix = format!("{}{}", options.base, prefix);
+                return serve_directory_listing(&prefix, root, path, query, strings).await;
+            }
+        }
+    } else if options.clean_urls && !req.uri().path().ends_with('/') && !path.exists() {
+        // Resolve `/about` to `/about.html`, like many static hosts do.
+        let mut html = path.clone().into_os_string();
+        html.push(".html");
+        let html = PathBuf::from(html);
+        if html.is_file() {
+            html
+        } else {
+            path
         }
     } else {
         path
     };
 
-    let mime = mime_guess::from_path(&path).first_or_text_plain();
+    // The index or the `.html` file may be a symlink as well.
+    if virtual_file.is_none() && escapes_root(root, &path) {
+        log::warn!("Refused to serve {:?} outside of the root", path);
+        return (StatusCode::FORBIDDEN, HeaderMap::new(), Body::empty());
+    }
+
+    let mime = guess_mime(&path);
     let mut headers = HeaderMap::new();
-    headers.append(
-        header::CONTENT_TYPE,
-        HeaderValue::from_str(mime.as_ref()).unwrap(),
-    );
+    headers.append(header::CONTENT_TYPE, content_type(&mime));
+
+    // Stream the large files sent as they are, rather than holding them in memory. The
+    // type of those without an extension is sniffed from their start.
+    let sniff = path.extension().is_none();
+    if virtual_file.is_none() && (sniff || !is_transformed(&mime)) {
+        #[cfg(feature = "mmap")]
+        if let Some(body) = options
+            .mmap
+            .and_then(|min_size| crate::mmap::map(&path, min_size))
+        {
+            let mime = match sniff {
+                true => sniff_mime(&body),
+                false => mime.clone(),
+            };
+            if !is_transformed(&mime) {
+                headers.insert(header::CONTENT_TYPE, content_type(&mime));
+                if let Some(etag) = std::fs::metadata(&path).ok().as_ref().and_then(file_etag) {
+                    headers.insert(header::ETAG, etag);
+                }
+                return (StatusCode::OK, headers, Body::from(body));
+            }
+        }
+        if let Some(response) = stream_file(&path, &mut headers, sniff).await {
+            return response;
+        }
+    }
 
     // Read the file.
-    let file = match fs::read(&path) {
+    let file = match virtual_file.map_or_else(|| fs::read(&path), Ok) {
         Ok(file) => file,
         Err(err) => {
             match path.to_str() {
@@ -141,27 +1088,69 @@ async fn static_assets(req: Request<Body>) -> (StatusCode, HeaderMap, Body) {
                 ErrorKind::NotFound => StatusCode::NOT_FOUND,
                 _ => StatusCode::INTERNAL_SERVER_ERROR,
             };
+            if let Some(response) = custom_error_page(status_code).await {
+                return response;
+            }
             if mime == "text/html" {
-                let script = format!(include_str!("templates/websocket.html"), addr);
-                let html = format!(include_str!("templates/error.html"), script, err);
-                let body = Body::from(html);
-
-                return (status_code, headers, body);
+                let strings = i18n::negotiate(options.lang.as_deref(), req.headers());
+                let message = match err.kind() {
+                    ErrorKind::NotFound => strings.not_found.to_string(),
+                    _ => err.to_string(),
+                };
+                return match error_html(strings, &message).await {
+                    Ok(html) => (status_code, headers, Body::from(html)),
+                    Err(err) => internal_err(err),
+                };
             }
             return (status_code, headers, Body::empty());
         }
     };
 
+    let mime = if path.extension().is_none() {
+        let mime = sniff_mime(&file);
+        headers.insert(header::CONTENT_TYPE, content_type(&mime));
+        mime
+    } else {
+        mime
+    };
+
+    let is_script = mime.subtype() == mime_guess::mime::JAVASCRIPT;
+    let file = match !options.pristine && options.env_vars && (mime == "text/html" || is_script) {
+        true => match String::from_utf8(file) {
+            Ok(text) => env_vars::substitute(&text).into_owned().into_bytes(),
+            Err(err) => err.into_bytes(),
+        },
+        false => file,
+    };
+
     // Construct the response.
-    let body = if mime == "text/html" && *WATCH.get().unwrap() {
-        let text = match String::from_utf8(file) {
+    let watch = *WATCH.get().unwrap();
+    let inject = !options.pristine
+        && (watch
+            || options.ssi
+            || options.preload_hints
+            || options.a11y
+            || options.client_log
+            || !options.snippets.is_empty()
+            || !options.html_transforms.is_empty());
+    let body = if mime == "text/html" && inject {
+        let mut text = match String::from_utf8(file) {
             Ok(text) => text,
             Err(err) => return internal_err(err),
         };
 
-        let script = format!(include_str!("templates/websocket.html"), addr);
+        if options.ssi {
+            text = process_includes(root, &path, &text);
+        }
+        if options.preload_hints {
+            text = preload::inject_hints(root, &path, text).await;
+        }
+        for HtmlTransform(transform) in &options.html_transforms {
+            text = transform(&request_path, text);
+        }
 
-        Body::from(format!("{text}{script}"))
+        inject_scripts(&mut text).await;
+        Body::from(text)
     } else {
         Body::from(file)
     };
@@ -169,14 +1158,325 @@ async fn static_assets(req: Request<Body>) -> (StatusCode, HeaderMap, Body) {
     (StatusCode::OK, headers, body)
 }
 
+/// Insert the built-in scripts and the snippets into the page.
+async fn inject_scripts(text: &mut String) {
+    let options = OPTIONS.get().unwrap();
+    let mut scripts = String::new();
+    if options.csp {
+        let nonce = csp_nonce(text)
+            .map(|nonce| format!(" nonce=\"{}\"", escape_html(nonce)))
+            .unwrap_or_default();
+        scripts.push_str(&format!(
+            "<script src=\"{}/_live-server/client.js\"{nonce}></script>\n",
+            options.base
+        ));
+    } else {
+        for script in client_scripts() {
+            scripts.push_str(&script_tag(&script));
+        }
+    }
+
+    for snippet in &options.snippets {
+        if let Some(html) = snippet.load().await {
+            scripts.push_str(&html);
+        }
+    }
+
+    insert_before_end(text, &scripts);
+}
+
+/// Render the built-in error page, which reloads like the other pages.
+async fn error_html(strings: &i18n::Strings, message: &str) -> std::io::Result<String> {
+    let html = get_error_html().await?;
+    let html = render(html, "script", reload_script());
+    let html = render(html, "base", &OPTIONS.get().unwrap().base);
+    let html = render(html, "error", escape_html(message));
+    Ok(i18n::translate(html, strings))
+}
+
+/// The page served while the root does not exist yet, e.g. `dist/` before the first
+/// build, which reloads once the watcher sees it created.
+async fn waiting_for_root(root: &Path, headers: &HeaderMap) -> (StatusCode, HeaderMap, Body) {
+    let options = OPTIONS.get().unwrap();
+    let strings = i18n::negotiate(options.lang.as_deref(), headers);
+    let message = strings.waiting(&path_to_string_but_readable(root));
+    let html = match error_html(strings, &message).await {
+        Ok(html) => html,
+        Err(err) => return internal_err(err),
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        content_type(&mime_guess::mime::TEXT_HTML),
+    );
+    (StatusCode::SERVICE_UNAVAILABLE, headers, Body::from(html))
+}
+
+/// Serve the page set by [`Listener::error_page`](crate::Listener::error_page) for the
+/// status code, or `None` if there is none.
+async fn custom_error_page(status_code: StatusCode) -> Option<(StatusCode, HeaderMap, Body)> {
+    let options = OPTIONS.get().unwrap();
+    let page = options.error_pages.get(&status_code.as_u16())?;
+    let mut html = match tokio::fs::read_to_string(page).await {
+        Ok(html) => html,
+        Err(err) => {
+            log::warn!("Failed to read the error page {:?}: {}", page, err);
+            return None;
+        }
+    };
+    if !options.pristine {
+        inject_scripts(&mut html).await;
+    }
+
+    let mut headers = HeaderMap::new();
+    headers.append(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    Some((status_code, headers, Body::from(html)))
+}
+
+async fn not_found() -> (StatusCode, HeaderMap, Body) {
+    custom_error_page(StatusCode::NOT_FOUND).await.unwrap_or((
+        StatusCode::NOT_FOUND,
+        HeaderMap::new(),
+        Body::empty(),
+    ))
+}
+
+/// Find the file whose path under the root matches ignoring the case, like on the
+/// case-insensitive filesystems of macOS and Windows.
+fn find_ignoring_case(root: &Path, path: &Path) -> Option<PathBuf> {
+    let mut found = root.to_path_buf();
+    for component in path.strip_prefix(root).ok()?.components() {
+        let Component::Normal(name) = component else {
+            return None;
+        };
+        let exact = found.join(name);
+        if exact.exists() {
+            found = exact;
+            continue;
+        }
+        let name = name.to_string_lossy().to_lowercase();
+        let entry = fs::read_dir(&found)
+            .ok()?
+            .filter_map(Result::ok)
+            .find(|entry| entry.file_name().to_string_lossy().to_lowercase() == name)?;
+        found = entry.path();
+    }
+    Some(found)
+}
+
+/// The file system path of the `?path=` parameter of the live-server routes, e.g. `docs`
+/// or `/docs` with the base path, or `None` if it would not be served.
+pub(crate) fn query_path(path: &str) -> Option<PathBuf> {
+    let options = OPTIONS.get().unwrap();
+    // The links in the listing include the base path, which the files are not under.
+    let path = format!("/{}", path.trim_start_matches('/'));
+    let path = path.strip_prefix(options.base.as_str()).unwrap_or(&path);
+    let (_, root, rest) = resolve_mount(path);
+    let resolved = root.join(rest.trim_start_matches('/'));
+    let show_dotfiles = options.show_dotfiles && !options.deny_dotfiles;
+    let refused = (!options.disk && root == *ROOT.get().unwrap())
+        || escapes_root(&root, &resolved)
+        || (!show_dotfiles && has_hidden_component(&root, &resolved));
+    (!refused).then_some(resolved)
+}
+
+/// Whether the path goes outside of the root through `..`, or through a symlink pointing
+/// outside of it unless allowed by [`Listener::follow_symlinks`](crate::Listener::follow_symlinks).
+pub(crate) fn escapes_root(root: &Path, path: &Path) -> bool {
+    if path
+        .components()
+        .any(|component| component == Component::ParentDir)
+    {
+        return true;
+    }
+    !OPTIONS.get().unwrap().follow_symlinks
+        && dunce::canonicalize(path).is_ok_and(|target| !target.starts_with(root))
+}
+
+/// Insert the HTML before `</body>`, or `</html>` if there is no body, so that the
+/// markup stays valid. Otherwise it is appended to the end.
+fn insert_before_end(text: &mut String, html: &str) {
+    let lowercase = text.to_ascii_lowercase();
+    let index = lowercase
+        .rfind("</body")
+        .or_else(|| lowercase.rfind("</html"))
+        .unwrap_or(text.len());
+    text.insert_str(index, html);
+}
+
+/// Guess the MIME type of the file by its extension, preferring the types set by
+/// [`Listener::mime_type`](crate::Listener::mime_type).
+/// Whether the files of the MIME type are changed before they are sent, e.g. the pages
+/// with the scripts injected, so that they cannot be streamed as they are.
+fn is_transformed(mime: &Mime) -> bool {
+    let options = OPTIONS.get().unwrap();
+    let is_script = mime.subtype() == mime_guess::mime::JAVASCRIPT;
+    !options.pristine && (*mime == "text/html" || options.env_vars && is_script)
+}
+
+pub(crate) fn guess_mime(path: &Path) -> Mime {
+    mime_override(path).unwrap_or_else(|| mime_guess::from_path(path).first_or_text_plain())
+}
+
+/// The `Content-Type` of the MIME type, with `charset=utf-8` for text unless disabled
+/// by [`Listener::charset`](crate::Listener::charset).
+pub(crate) fn content_type(mime: &Mime) -> HeaderValue {
+    let is_text = mime.type_() == mime_guess::mime::TEXT
+        || matches!(mime.subtype().as_str(), "javascript" | "json" | "xml")
+        || matches!(
+            mime.suffix().map(|name| name.as_str()),
+            Some("json" | "xml")
+        );
+    if OPTIONS.get().unwrap().charset && is_text && mime.get_param("charset").is_none() {
+        HeaderValue::from_str(&format!("{mime}; charset=utf-8")).unwrap()
+    } else {
+        HeaderValue::from_str(mime.as_ref()).unwrap()
+    }
+}
+
+/// The size from which the files sent as they are get streamed from the disk.
+const STREAM_THRESHOLD: u64 = 1024 * 1024;
+
+/// The size of the pieces read from the disk while streaming.
+const STREAM_CHUNK: usize = 256 * 1024;
+
+/// Send the file in pieces as they are read, so that serving multi-GB artifacts does not
+/// load them into memory. `None` for the small files, or if the file cannot be opened,
+/// which the caller reports.
+async fn stream_file(
+    path: &Path,
+    headers: &mut HeaderMap,
+    sniff: bool,
+) -> Option<(StatusCode, HeaderMap, Body)> {
+    let mut file = tokio::fs::File::open(path).await.ok()?;
+    let metadata = file.metadata().await.ok()?;
+    if !metadata.is_file() || metadata.len() < STREAM_THRESHOLD {
+        return None;
+    }
+    let mut head = Vec::with_capacity(SNIFF_SIZE);
+    if sniff {
+        (&mut file)
+            .take(SNIFF_SIZE as u64)
+            .read_to_end(&mut head)
+            .await
+            .ok()?;
+        let mime = sniff_mime(&head);
+        // A page without an extension still gets the scripts injected.
+        if is_transformed(&mime) {
+            return None;
+        }
+        headers.insert(header::CONTENT_TYPE, content_type(&mime));
+    }
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(metadata.len()));
+    if let Some(etag) = file_etag(&metadata) {
+        headers.insert(header::ETAG, etag);
+    }
+    let head = futures::stream::once(async { Ok(axum::body::Bytes::from(head)) });
+    let stream = head.chain(ReaderStream::with_capacity(file, STREAM_CHUNK));
+    Some((
+        StatusCode::OK,
+        std::mem::take(headers),
+        Body::from_stream(stream),
+    ))
+}
+
+/// Magic numbers of the common binary formats.
+const MAGIC_NUMBERS: [(&[u8], &str); 10] = [
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF8", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"\0asm", "application/wasm"),
+    (b"wOFF", "font/woff"),
+    (b"wOF2", "font/woff2"),
+    (b"\x7fELF", "application/octet-stream"),
+];
+
+/// How much of the start of a file is looked at to guess its MIME type.
+const SNIFF_SIZE: usize = 1024;
+
+/// Guess the MIME type of a file without extension, e.g. `LICENSE` or a hashed build
+/// output, by its content, so that binaries are downloaded instead of shown as text.
+fn sniff_mime(content: &[u8]) -> Mime {
+    if let Some((_, mime)) = MAGIC_NUMBERS
+        .iter()
+        .find(|(magic, _)| content.starts_with(magic))
+    {
+        return mime.parse().unwrap();
+    }
+    if content.starts_with(b"RIFF") && content.get(8..12) == Some(b"WEBP") {
+        return "image/webp".parse().unwrap();
+    }
+
+    let head = &content[..content.len().min(SNIFF_SIZE)];
+    if head.contains(&0) {
+        return mime_guess::mime::APPLICATION_OCTET_STREAM;
+    }
+    // The head may end in the middle of a character.
+    if std::str::from_utf8(head).is_err_and(|err| err.error_len().is_some()) {
+        return mime_guess::mime::APPLICATION_OCTET_STREAM;
+    }
+
+    let text = String::from_utf8_lossy(head)
+        .trim_start()
+        .to_ascii_lowercase();
+    if text.starts_with("<!doctype html") || text.starts_with("<html") {
+        mime_guess::mime::TEXT_HTML
+    } else {
+        mime_guess::mime::TEXT_PLAIN
+    }
+}
+
+/// The MIME type set by [`Listener::mime_type`](crate::Listener::mime_type) for the file.
+pub(crate) fn mime_override(path: &Path) -> Option<Mime> {
+    let overrides = &OPTIONS.get().unwrap().mime_types;
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    let mime = overrides.get(&ext)?;
+    match mime.parse() {
+        Ok(mime) => Some(mime),
+        Err(err) => {
+            log::warn!("Invalid MIME type {:?}: {}", mime, err);
+            None
+        }
+    }
+}
+
 fn static_router() -> Router {
-    Router::new()
+    let router = Router::new()
         .route("/index.css", get(|r| asset(r, get_index_css)))
+        .route("/theme.js", get(|r| asset(r, get_theme_js)))
+        .route("/health", get(health))
+        .route("/hash", get(hash))
+        .route("/stats", get(stats::serve_stats))
+        .route("/client.js", get(client_bundle))
+        .route("/events", get(events))
+        .route("/poll", get(poll))
+        .route("/api/a11y", get(list_reports).post(receive_report))
+        .route("/client-log", post(receive_error))
+        .nest("/admin", admin_router());
+
+    #[cfg(feature = "listing")]
+    let router = router
         .route("/dir.svg", get(|r| asset(r, get_dir_svg)))
         .route("/file.svg", get(|r| asset(r, get_file_svg)))
         .route("/dir-link.svg", get(|r| asset(r, get_dir_link_svg)))
         .route("/file-link.svg", get(|r| asset(r, get_file_link_svg)))
         .route("/unknown.svg", get(|r| asset(r, get_unknown_svg)))
+        .route("/preview", get(preview))
+        .route("/archive", get(archive));
+
+    #[cfg(feature = "webdav")]
+    let router = router
+        .route("/webdav", any(crate::webdav::handle))
+        .route("/webdav/", any(crate::webdav::handle))
+        .route("/webdav/*path", any(crate::webdav::handle));
+
+    router
 }
 
 async fn asset<F, Fut>(req: Request<Body>, content_fn: F) -> (StatusCode, HeaderMap, Body)
@@ -204,10 +1504,11 @@ where
 
 fn mime_type(ext: &str) -> &str {
     match ext {
-        "css" => "text/css",
-        "html" => "text/html",
+        "css" => "text/css; charset=utf-8",
+        "js" => "text/javascript; charset=utf-8",
+        "html" => "text/html; charset=utf-8",
         "svg" => "image/svg+xml",
-        _ => "text/plain",
+        _ => "text/plain; charset=utf-8",
     }
 }
 
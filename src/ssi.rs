@@ -0,0 +1,58 @@
+use std::{fs, path::Path, sync::LazyLock};
+
+use regex::{Captures, Regex};
+
+use crate::server::escapes_root;
+use crate::util::has_hidden_component;
+use crate::OPTIONS;
+
+/// Stop expanding the includes nested deeper than this, in case a file includes itself.
+const MAX_DEPTH: usize = 8;
+
+/// What Apache shows in place of a directive which fails.
+const ERROR_MESSAGE: &str = "[an error occurred while processing this directive]";
+
+static INCLUDE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)<!--#include\s+(file|virtual)\s*=\s*"([^"]*)"\s*-->"#).unwrap()
+});
+
+/// Replace the `<!--#include file="..." -->` and `<!--#include virtual="..." -->` directives
+/// with the content of the files, like a server with server-side includes would. `file` is
+/// relative to the page, and `virtual` is a URL path relative to the root.
+pub(crate) fn process_includes(root: &Path, html_path: &Path, html: &str) -> String {
+    expand(root, html_path, html, 0)
+}
+
+fn expand(root: &Path, html_path: &Path, html: &str, depth: usize) -> String {
+    INCLUDE
+        .replace_all(html, |caps: &Captures| {
+            let target = &caps[2];
+            let path = match caps[1].eq_ignore_ascii_case("virtual") || target.starts_with('/') {
+                true => root.join(target.trim_start_matches('/')),
+                false => html_path.parent().unwrap_or(root).join(target),
+            };
+
+            if depth >= MAX_DEPTH {
+                log::warn!("[SSI] Includes nested too deeply at {:?}", path);
+                return ERROR_MESSAGE.to_string();
+            }
+            if !path.starts_with(root) || escapes_root(root, &path) {
+                log::warn!("[SSI] Refused to include {:?} outside of the root", path);
+                return ERROR_MESSAGE.to_string();
+            }
+            let options = OPTIONS.get().unwrap();
+            let show_dotfiles = options.show_dotfiles && !options.deny_dotfiles;
+            if !show_dotfiles && has_hidden_component(root, &path) {
+                log::warn!("[SSI] Refused to include the dotfile {:?}", path);
+                return ERROR_MESSAGE.to_string();
+            }
+            match fs::read_to_string(&path) {
+                Ok(content) => expand(root, &path, &content, depth + 1),
+                Err(err) => {
+                    log::warn!("[SSI] Failed to include {:?}: {}", path, err);
+                    ERROR_MESSAGE.to_string()
+                }
+            }
+        })
+        .into_owned()
+}
@@ -1,24 +1,109 @@
+use crate::STORAGE;
+
+/// The embedded files which can be replaced at runtime.
+pub(crate) const EMBEDDED_FILES: &[&str] = &[
+    "public/index.css",
+    "public/theme.js",
+    "templates/error.html",
+    #[cfg(feature = "listing")]
+    "templates/entry.html",
+    #[cfg(feature = "listing")]
+    "templates/listing.html",
+    #[cfg(feature = "listing")]
+    "public/dir.svg",
+    #[cfg(feature = "listing")]
+    "public/file.svg",
+    #[cfg(feature = "listing")]
+    "public/dir_link.svg",
+    #[cfg(feature = "listing")]
+    "public/file_link.svg",
+    #[cfg(feature = "listing")]
+    "public/unknown.svg",
+];
+
+/// Replace the content of an embedded file, or restore it if `content` is `None`.
+pub(crate) fn set_override(filename: &str, content: Option<String>) -> std::io::Result<()> {
+    let storage = STORAGE.get().unwrap();
+    let key = format!("overrides/{filename}");
+    match content {
+        Some(content) => storage.put(&key, content.as_bytes()),
+        None => storage.remove(&key),
+    }
+}
+
+fn get_override(filename: &str) -> Option<String> {
+    let storage = STORAGE.get()?;
+    match storage.get(&format!("overrides/{filename}")) {
+        Ok(content) => content.map(|content| String::from_utf8_lossy(&content).to_string()),
+        Err(err) => {
+            log::warn!("Failed to read the replaced {}: {}", filename, err);
+            None
+        }
+    }
+}
+
 macro_rules! embed_file {
     ($name:ident, $filename:expr) => {
         #[cfg(debug_assertions)]
         pub async fn $name() -> Result<String, std::io::Error> {
+            if let Some(content) = get_override($filename) {
+                return Ok(content);
+            }
             tokio::fs::read_to_string(concat!("src/", $filename)).await
         }
 
         #[cfg(not(debug_assertions))]
         pub async fn $name() -> Result<String, std::io::Error> {
+            if let Some(content) = get_override($filename) {
+                return Ok(content);
+            }
             Ok(include_str!($filename).to_owned())
         }
     };
 }
 
+/// Get the current content of an embedded file by its name.
+pub(crate) async fn get_embedded_file(filename: &str) -> Option<Result<String, std::io::Error>> {
+    Some(match filename {
+        "public/index.css" => get_index_css().await,
+        "public/theme.js" => get_theme_js().await,
+        "templates/error.html" => get_error_html().await,
+        #[cfg(feature = "listing")]
+        "templates/entry.html" => get_entry_html().await,
+        #[cfg(feature = "listing")]
+        "templates/listing.html" => get_listing_html().await,
+        #[cfg(feature = "listing")]
+        "public/dir.svg" => get_dir_svg().await,
+        #[cfg(feature = "listing")]
+        "public/file.svg" => get_file_svg().await,
+        #[cfg(feature = "listing")]
+        "public/dir_link.svg" => get_dir_link_svg().await,
+        #[cfg(feature = "listing")]
+        "public/file_link.svg" => get_file_link_svg().await,
+        #[cfg(feature = "listing")]
+        "public/unknown.svg" => get_unknown_svg().await,
+        _ => return None,
+    })
+}
+
 embed_file!(get_index_css, "public/index.css");
+embed_file!(get_theme_js, "public/theme.js");
 
-embed_file!(get_entry_html, "templates/entry.html");
-embed_file!(get_listing_html, "templates/listing.html");
+embed_file!(get_error_html, "templates/error.html");
 
-embed_file!(get_dir_svg, "public/dir.svg");
-embed_file!(get_file_svg, "public/file.svg");
-embed_file!(get_dir_link_svg, "public/dir_link.svg");
-embed_file!(get_file_link_svg, "public/file_link.svg");
-embed_file!(get_unknown_svg, "public/unknown.svg");
+// The directory listing, which the `listing` feature can leave out of the binary.
+#[cfg(feature = "listing")]
+mod listing {
+    use super::get_override;
+
+    embed_file!(get_entry_html, "templates/entry.html");
+    embed_file!(get_listing_html, "templates/listing.html");
+
+    embed_file!(get_dir_svg, "public/dir.svg");
+    embed_file!(get_file_svg, "public/file.svg");
+    embed_file!(get_dir_link_svg, "public/dir_link.svg");
+    embed_file!(get_file_link_svg, "public/file_link.svg");
+    embed_file!(get_unknown_svg, "public/unknown.svg");
+}
+#[cfg(feature = "listing")]
+pub(crate) use listing::*;
@@ -0,0 +1,330 @@
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::{Body, HttpBody},
+    extract::{ConnectInfo, Request},
+    http::{header, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+    Json,
+};
+use futures::StreamExt;
+use serde::{Serialize, Serializer};
+
+use crate::global::Global;
+use crate::health::{watcher_status, WatcherStatus};
+use crate::server::is_internal;
+use crate::{OPTIONS, TX};
+
+pub(crate) static STATS: Counters = Counters::new();
+
+static X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+static STARTED: Global<Instant> = Global::new();
+
+/// How many clients the traffic is kept for, forgetting the one seen the longest ago, so
+/// that a server open to a network does not keep growing.
+const MAX_CLIENTS: usize = 1024;
+
+pub(crate) struct Counters {
+    requests: AtomicU64,
+    statuses: Mutex<BTreeMap<u16, u64>>,
+    /// Requests with `If-None-Match` or `If-Modified-Since`, which may be answered with 304.
+    conditional: AtomicU64,
+    bytes: AtomicU64,
+    reloads: AtomicU64,
+    errors: AtomicU64,
+    /// The traffic of each client, and when it was last seen.
+    clients: Mutex<BTreeMap<IpAddr, (ClientStats, Instant)>>,
+}
+
+impl Counters {
+    const fn new() -> Self {
+        Counters {
+            requests: AtomicU64::new(0),
+            statuses: Mutex::new(BTreeMap::new()),
+            conditional: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            reloads: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            clients: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub(crate) fn start(&self) {
+        STARTED.set(Instant::now());
+        self.requests.store(0, Ordering::Relaxed);
+        self.statuses.lock().unwrap().clear();
+        self.conditional.store(0, Ordering::Relaxed);
+        self.bytes.store(0, Ordering::Relaxed);
+        self.reloads.store(0, Ordering::Relaxed);
+        self.errors.store(0, Ordering::Relaxed);
+        self.clients.lock().unwrap().clear();
+    }
+
+    pub(crate) fn add_bytes(&self, client: Option<IpAddr>, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        if let Some(ip) = client {
+            self.update_client(ip, |client| client.bytes += bytes);
+        }
+    }
+
+    fn add_request(&self, client: Option<IpAddr>, status: StatusCode, conditional: bool) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        *self
+            .statuses
+            .lock()
+            .unwrap()
+            .entry(status.as_u16())
+            .or_default() += 1;
+        if conditional {
+            self.conditional.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(ip) = client {
+            self.update_client(ip, |client| client.requests += 1);
+        }
+    }
+
+    fn update_client(&self, ip: IpAddr, update: impl FnOnce(&mut ClientStats)) {
+        let mut clients = self.clients.lock().unwrap();
+        if clients.len() >= MAX_CLIENTS && !clients.contains_key(&ip) {
+            let oldest = clients
+                .iter()
+                .min_by_key(|(_, (_, seen))| *seen)
+                .map(|(ip, _)| *ip);
+            if let Some(oldest) = oldest {
+                clients.remove(&oldest);
+            }
+        }
+        let (client, seen) = clients
+            .entry(ip)
+            .or_insert_with(|| (ClientStats::new(ip), Instant::now()));
+        update(client);
+        *seen = Instant::now();
+    }
+
+    pub(crate) fn add_reload(&self) {
+        self.reloads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// A snapshot of the counters collected since the server started.
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    /// Time elapsed since the server started.
+    #[serde(serialize_with = "serialize_secs")]
+    pub uptime: Duration,
+    /// Number of HTTP requests handled.
+    pub requests: u64,
+    /// Number of HTTP requests handled by the status of their response.
+    pub statuses: BTreeMap<u16, u64>,
+    /// Share of the conditional requests answered with 304 Not Modified, from 0 to 1.
+    /// The files and the mocks answer 304 when their `ETag` still matches.
+    pub cache_hit_ratio: f64,
+    /// Number of response body bytes sent.
+    pub bytes: u64,
+    /// Number of reloads broadcast to the clients.
+    pub reloads: u64,
+    /// Number of server errors and watcher errors.
+    pub errors: u64,
+    /// Number of pages currently connected for live reload.
+    pub connections: usize,
+    /// Traffic of each client, sorted by bytes sent in descending order. Only the last
+    /// 1024 clients seen are kept.
+    pub clients: Vec<ClientStats>,
+    /// What the file watcher has been doing, as in [`watcher_status`](crate::watcher_status).
+    pub watcher: WatcherStatus,
+}
+
+/// The traffic of a single client IP address.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientStats {
+    pub ip: IpAddr,
+    /// Number of HTTP requests made by the client.
+    pub requests: u64,
+    /// Number of response body bytes sent to the client.
+    pub bytes: u64,
+}
+
+impl ClientStats {
+    fn new(ip: IpAddr) -> Self {
+        ClientStats {
+            ip,
+            requests: 0,
+            bytes: 0,
+        }
+    }
+}
+
+/// A request handled by the server, as passed to [`Listener::on_request`](crate::Listener::on_request)
+/// and [`Listener::on_request_async`](crate::Listener::on_request_async).
+#[derive(Debug, Clone)]
+pub struct RequestSummary {
+    pub method: String,
+    /// The path and query of the request below the base path, e.g. `/index.html?v=2`.
+    pub uri: String,
+    pub status: u16,
+    /// Time taken until the response headers, not counting a streamed body.
+    pub duration: Duration,
+    pub client: Option<IpAddr>,
+    /// The `X-Request-Id` of the request if it had one, or the one generated for it,
+    /// which is sent back in the response.
+    pub request_id: String,
+}
+
+impl RequestSummary {
+    /// The path of the request without the query, e.g. `/index.html`.
+    pub fn path(&self) -> &str {
+        self.uri.split_once('?').map_or(&self.uri, |(path, _)| path)
+    }
+}
+
+/// A callback of [`Listener::on_request`](crate::Listener::on_request).
+#[derive(Clone)]
+pub(crate) struct RequestHook(pub(crate) Arc<dyn Fn(&RequestSummary) + Send + Sync>);
+
+impl std::fmt::Debug for RequestHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RequestHook")
+    }
+}
+
+/// Return the statistics collected since the server started, which are also served as
+/// JSON at `/_live-server/stats`.
+///
+/// ```
+/// let stats = live_server::stats();
+/// println!("{} requests served", stats.requests);
+/// ```
+pub fn stats() -> Stats {
+    let mut clients: Vec<ClientStats> = STATS
+        .clients
+        .lock()
+        .unwrap()
+        .values()
+        .map(|(client, _)| client.clone())
+        .collect();
+    clients.sort_by_key(|client| std::cmp::Reverse(client.bytes));
+    let statuses = STATS.statuses.lock().unwrap().clone();
+    let not_modified = statuses.get(&304).copied().unwrap_or_default();
+    let conditional = STATS.conditional.load(Ordering::Relaxed);
+
+    Stats {
+        uptime: STARTED
+            .get()
+            .map(|started| started.elapsed())
+            .unwrap_or_default(),
+        requests: STATS.requests.load(Ordering::Relaxed),
+        statuses,
+        cache_hit_ratio: match conditional {
+            0 => 0.0,
+            conditional => not_modified.min(conditional) as f64 / conditional as f64,
+        },
+        bytes: STATS.bytes.load(Ordering::Relaxed),
+        reloads: STATS.reloads.load(Ordering::Relaxed),
+        errors: STATS.errors.load(Ordering::Relaxed),
+        connections: TX.get().map_or(0, |tx| tx.receiver_count()),
+        clients,
+        watcher: watcher_status(),
+    }
+}
+
+/// Answer [`stats`] as JSON at `/_live-server/stats`.
+pub(crate) async fn serve_stats() -> Json<Stats> {
+    Json(stats())
+}
+
+pub(crate) fn serialize_secs<S: Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+pub(crate) async fn track(req: Request, next: Next) -> Response {
+    let client = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        // The IPv4 clients of a dual-stack listener show as IPv4-mapped IPv6 addresses.
+        .map(|ConnectInfo(addr)| addr.ip().to_canonical());
+    let method = req.method().clone();
+    let uri = req.uri().clone();
+    let start = Instant::now();
+    let request_id = request_id(&req);
+    let conditional = req.headers().contains_key(header::IF_NONE_MATCH)
+        || req.headers().contains_key(header::IF_MODIFIED_SINCE);
+
+    let mut response = next.run(req).await;
+    log::debug!(
+        "[REQUEST] {} {} {} {}",
+        request_id.to_str().unwrap_or_default(),
+        method,
+        uri,
+        response.status().as_u16()
+    );
+    let options = OPTIONS.get();
+    let hooks = options
+        .as_ref()
+        .map(|options| options.on_request.as_slice())
+        .unwrap_or_default();
+    if !hooks.is_empty() && !is_internal(uri.path()) {
+        let summary = RequestSummary {
+            method: method.to_string(),
+            uri: uri.to_string(),
+            status: response.status().as_u16(),
+            duration: start.elapsed(),
+            client,
+            request_id: request_id.to_str().unwrap_or_default().to_string(),
+        };
+        for RequestHook(hook) in hooks {
+            hook(&summary);
+        }
+    }
+
+    response
+        .headers_mut()
+        .insert(X_REQUEST_ID.clone(), request_id);
+    STATS.add_request(client, response.status(), conditional);
+    if response.status().is_server_error() {
+        STATS.add_error();
+    }
+
+    match response.body().size_hint().exact() {
+        Some(bytes) => {
+            STATS.add_bytes(client, bytes);
+            response
+        }
+        // Count streamed bodies as they are sent.
+        None => response.map(|body| {
+            Body::from_stream(body.into_data_stream().inspect(move |chunk| {
+                if let Ok(chunk) = chunk {
+                    STATS.add_bytes(client, chunk.len() as u64);
+                }
+            }))
+        }),
+    }
+}
+
+/// The `X-Request-Id` of the request, e.g. set by another local service, so that their
+/// logs can be matched, or a new random one.
+fn request_id(req: &Request) -> HeaderValue {
+    let incoming = req.headers().get(&X_REQUEST_ID).filter(|value| {
+        let len = value.len();
+        (1..=128).contains(&len) && value.to_str().is_ok()
+    });
+    match incoming {
+        Some(value) => value.clone(),
+        None => HeaderValue::from_str(&format!("{:016x}", fastrand::u64(..))).unwrap(),
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufRead, BufReader, ErrorKind},
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use sha2::{Digest, Sha256};
+
+/// A key-value store for the state of the server, such as the replaced templates
+/// and the accessibility reports.
+///
+/// The state is kept in memory by default. Use [`DiskStorage`] or your own
+/// implementation to persist it across restarts.
+pub trait Storage: Send + Sync {
+    /// Get the value of the key, or `None` if it does not exist.
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>>;
+    /// Set the value of the key.
+    fn put(&self, key: &str, value: &[u8]) -> io::Result<()>;
+    /// Remove the key if it exists.
+    fn remove(&self, key: &str) -> io::Result<()>;
+    /// List the keys starting with the prefix.
+    fn keys(&self, prefix: &str) -> io::Result<Vec<String>>;
+}
+
+/// Keep the state in memory, so it is lost when the server stops.
+#[derive(Default)]
+pub struct MemoryStorage {
+    entries: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.entries.lock().unwrap().get(key).cloned())
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> io::Result<()> {
+        self.entries.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    fn keys(&self, prefix: &str) -> io::Result<Vec<String>> {
+        let entries = self.entries.lock().unwrap();
+        Ok(entries
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+}
+
+/// Keep the state as files in a directory, one file per key.
+///
+/// The long keys, which would not fit in a file name, are shortened with a hash, and
+/// kept on the first line of the file.
+pub struct DiskStorage {
+    dir: PathBuf,
+}
+
+/// Characters to encode in the file names, so that any key maps to a single file.
+/// `.` is encoded as well to avoid the special `.` and `..` names.
+const FILE_NAME: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_');
+
+/// The longest file name used as is, below the 255 bytes most file systems allow.
+const MAX_FILE_NAME: usize = 200;
+
+/// How much of a long key is kept in its file name, before `~` and the hash, so that the
+/// files can still be told apart.
+const LONG_KEY_PREFIX: usize = 100;
+
+impl DiskStorage {
+    /// Store the state in the directory, which is created if it does not exist.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(DiskStorage { dir })
+    }
+
+    /// The file of the key, and whether the key is kept in it as it is a long one.
+    fn path(&self, key: &str) -> (PathBuf, bool) {
+        let name = utf8_percent_encode(key, FILE_NAME).to_string();
+        if name.len() <= MAX_FILE_NAME {
+            return (self.dir.join(name), false);
+        }
+        // `~` is encoded in the other names, so these cannot be mistaken for them.
+        let hash = Sha256::digest(key.as_bytes());
+        let name = format!("{}~{:x}", &name[..LONG_KEY_PREFIX], hash);
+        (self.dir.join(name), true)
+    }
+}
+
+/// The encoded key on the first line of the file of a long key.
+fn key_line(key: &str) -> String {
+    format!("{}\n", utf8_percent_encode(key, FILE_NAME))
+}
+
+impl Storage for DiskStorage {
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        let (path, long) = self.path(key);
+        let mut value = match std::fs::read(path) {
+            Ok(value) => value,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        if long {
+            let line = key_line(key);
+            if !value.starts_with(line.as_bytes()) {
+                return Ok(None);
+            }
+            value.drain(..line.len());
+        }
+        Ok(Some(value))
+    }
+
+    fn put(&self, key: &str, value: &[u8]) -> io::Result<()> {
+        let (path, long) = self.path(key);
+        match long {
+            true => std::fs::write(path, [key_line(key).as_bytes(), value].concat()),
+            false => std::fs::write(path, value),
+        }
+    }
+
+    fn remove(&self, key: &str) -> io::Result<()> {
+        match std::fs::remove_file(self.path(key).0) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    fn keys(&self, prefix: &str) -> io::Result<Vec<String>> {
+        let mut keys = vec![];
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let mut name = entry.file_name().to_string_lossy().to_string();
+            if name.contains('~') {
+                name.clear();
+                BufReader::new(File::open(entry.path())?).read_line(&mut name)?;
+                name.truncate(name.trim_end_matches('\n').len());
+            }
+            let key = percent_decode_str(&name).decode_utf8_lossy().to_string();
+            if key.starts_with(prefix) {
+                keys.push(key);
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
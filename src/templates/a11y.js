@@ -0,0 +1,10 @@
+window.addEventListener("load", () => {
+    if (!window.axe) return;
+    axe.run().then((results) =>
+        fetch("{{ base }}/_live-server/api/a11y", {
+            method: "POST",
+            headers: { "Content-Type": "application/json" },
+            body: JSON.stringify({ url: location.href, violations: results.violations }),
+        })
+    );
+});
@@ -0,0 +1,24 @@
+(() => {
+    const report = (error) =>
+        fetch("{{ base }}/_live-server/client-log", {
+            method: "POST",
+            headers: { "Content-Type": "application/json" },
+            body: JSON.stringify({ url: location.href, ...error }),
+        }).catch(() => {});
+
+    window.addEventListener("error", (event) =>
+        report({
+            message: event.message,
+            source: event.filename || null,
+            line: event.lineno || null,
+            column: event.colno || null,
+            stack: event.error?.stack ?? null,
+        })
+    );
+    window.addEventListener("unhandledrejection", (event) =>
+        report({
+            message: `Unhandled rejection: ${event.reason?.message ?? event.reason}`,
+            stack: event.reason?.stack ?? null,
+        })
+    );
+})();
@@ -0,0 +1,136 @@
+(() => {
+    let connected = false;
+    let retryDelay = 500;
+
+    function connect() {
+        const protocol = location.protocol === "https:" ? "wss:" : "ws:";
+        const ws = new WebSocket({{ ws_url }} ?? `${protocol}//${location.host}{{ base }}/live-server-ws`);
+        ws.onopen = () => {
+            console.log("[Live Server] Connection Established");
+            // The server has restarted, so the page may be stale.
+            if (connected) location.reload();
+            connected = true;
+            retryDelay = 500;
+            setStatus(true);
+        };
+        ws.onmessage = (event) => handleMessage(event.data);
+        ws.onclose = () => {
+            if (connected) {
+                setStatus(false);
+                console.log(`[Live Server] Connection Closed, reconnecting in ${retryDelay}ms`);
+                setTimeout(connect, retryDelay);
+                retryDelay = Math.min(retryDelay * 2, 10000);
+                return;
+            }
+            fallback();
+        };
+    }
+    connect();
+
+    function fallback() {
+        // The upgrade may be blocked by a proxy, so try Server-Sent Events instead.
+        const events = new EventSource("{{ base }}/_live-server/events");
+        events.onopen = () => {
+            console.log("[Live Server] Connection Established (SSE)");
+            // EventSource reconnects by itself after the server restarts.
+            if (connected) location.reload();
+            connected = true;
+            setStatus(true);
+        };
+        events.onmessage = (event) => handleMessage(event.data);
+        events.onerror = () => {
+            if (connected) {
+                setStatus(false);
+                return;
+            }
+            // Fall back to long polling as the last resort.
+            events.close();
+            console.log("[Live Server] Connection Established (long polling)");
+            setStatus(true);
+            poll();
+        };
+    }
+
+    async function poll() {
+        let failed = false;
+        // The number of the last message, so that those sent between two requests are not lost.
+        let cursor = null;
+        while (true) {
+            try {
+                const query = cursor === null ? "" : `?since=${cursor}`;
+                const response = await fetch(`{{ base }}/_live-server/poll${query}`, { cache: "no-store" });
+                if (failed) location.reload();
+                setStatus(true);
+                cursor = response.headers.get("x-live-server-cursor") ?? cursor;
+                if (response.status === 200) {
+                    handleMessage(await response.text());
+                    continue;
+                }
+                if (response.status === 204) continue;
+            } catch {
+                failed = true;
+                setStatus(false);
+            }
+            await new Promise((resolve) => setTimeout(resolve, 1000));
+        }
+    }
+
+    // Let the other injected scripts, e.g. the status badge, follow the connection.
+    function setStatus(connected) {
+        window.dispatchEvent(new CustomEvent("live-server:status", { detail: { connected } }));
+    }
+
+    function handleMessage(data) {
+        const message = JSON.parse(data);
+        switch (message.type) {
+            case "reload":
+                location.reload();
+                break;
+            case "css-update":
+                hideError();
+                updateStylesheets(message.paths);
+                break;
+            case "error":
+                showError(message.message);
+                break;
+        }
+    }
+
+    function showError(text) {
+        hideError();
+        const overlay = document.createElement("div");
+        overlay.id = "live-server-error";
+        overlay.style.cssText =
+            "position:fixed;inset:0;z-index:2147483647;overflow:auto;padding:2rem;" +
+            "background:rgba(0,0,0,0.85);color:#ff8a80;font:14px monospace";
+        const close = document.createElement("button");
+        close.textContent = "×";
+        close.style.cssText =
+            "position:absolute;top:1rem;right:1rem;font-size:1.5rem;color:white;" +
+            "background:none;border:none;cursor:pointer";
+        close.onclick = hideError;
+        const pre = document.createElement("pre");
+        pre.style.whiteSpace = "pre-wrap";
+        pre.textContent = text;
+        overlay.append(close, pre);
+        document.body.appendChild(overlay);
+    }
+
+    function hideError() {
+        document.getElementById("live-server-error")?.remove();
+    }
+
+    function updateStylesheets(paths) {
+        let updated = false;
+        for (const link of document.querySelectorAll('link[rel="stylesheet"]')) {
+            const url = new URL(link.href);
+            if (url.host === location.host && paths.includes(decodeURIComponent(url.pathname))) {
+                url.searchParams.set("live-server", Date.now());
+                link.href = url.href;
+                updated = true;
+            }
+        }
+        // The stylesheet may be imported by another one or not be used by the page.
+        if (!updated) location.reload();
+    }
+})();
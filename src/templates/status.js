@@ -0,0 +1,22 @@
+(() => {
+    let badge;
+    let dismissed = false;
+    window.addEventListener("live-server:status", (event) => {
+        if (dismissed) return;
+        if (!badge) {
+            badge = document.createElement("div");
+            badge.title = "Click to dismiss";
+            badge.style.cssText =
+                "position:fixed;right:12px;bottom:12px;z-index:2147483647;padding:4px 10px;" +
+                "border-radius:12px;font:12px sans-serif;color:white;cursor:pointer;opacity:0.85";
+            badge.onclick = () => {
+                dismissed = true;
+                badge.remove();
+            };
+            document.body.appendChild(badge);
+        }
+        const { connected } = event.detail;
+        badge.textContent = connected ? "live reload connected" : "live reload disconnected";
+        badge.style.backgroundColor = connected ? "#2e7d32" : "#c62828";
+    });
+})();
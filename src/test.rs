@@ -0,0 +1,192 @@
+//! Running the server in integration tests, on a free port of the loopback interface.
+//!
+//! The server keeps its state in globals, so the servers of the tests running in
+//! parallel take turns: each one starts once the [`TestServer`] before it is dropped.
+//! **A test can therefore only run one server at a time.** Spawning another one while
+//! the test still holds a [`TestServer`] fails after a few seconds, rather than waiting
+//! forever for a turn that would never come.
+//!
+//! ```
+//! use live_server::test::spawn_temp;
+//!
+//! async fn serves_the_index() -> Result<(), Box<dyn std::error::Error>> {
+//!     let server = spawn_temp("./tests/page").await?;
+//!     let url = server.url("/index.html");
+//!     // Request `url` with any HTTP client, the server stops when `server` is dropped.
+//!     Ok(())
+//! }
+//! ```
+
+use std::{
+    error::Error,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, LazyLock},
+    thread::{self, ThreadId},
+    time::Duration,
+};
+
+use tokio::{
+    runtime::Handle,
+    sync::{Mutex, OwnedMutexGuard},
+    task::JoinHandle,
+    time::timeout,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{listen, Listener};
+
+/// Held by the running test server, so that the next one waits for it to stop.
+static TURN: LazyLock<Arc<Mutex<()>>> = LazyLock::new(|| Arc::new(Mutex::new(())));
+
+/// How long a test waits for a turn it holds itself, before giving up.
+const OWN_TURN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The thread of the test holding the turn, which would wait for itself if it spawned
+/// another server.
+static HOLDER: std::sync::Mutex<Option<ThreadId>> = std::sync::Mutex::new(None);
+
+/// The turn of a server, given to the next one when dropped.
+#[derive(Debug)]
+struct Turn {
+    _guard: OwnedMutexGuard<()>,
+}
+
+impl Drop for Turn {
+    fn drop(&mut self) {
+        *HOLDER.lock().unwrap() = None;
+    }
+}
+
+/// A server started by [`spawn_temp`] or [`spawn_with`], which shuts down when dropped.
+#[derive(Debug)]
+pub struct TestServer {
+    addr: SocketAddr,
+    url: String,
+    shutdown: CancellationToken,
+    task: Option<JoinHandle<()>>,
+    turn: Option<Turn>,
+}
+
+impl TestServer {
+    /// The address the server is bound to, with the port chosen by the system.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The URL of the root, without a trailing slash, e.g. `http://127.0.0.1:40123`.
+    pub fn base_url(&self) -> &str {
+        &self.url
+    }
+
+    /// The URL of the path, e.g. `http://127.0.0.1:40123/index.html` for `/index.html`.
+    pub fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.url, path.trim_start_matches('/'))
+    }
+
+    /// Shut down and wait until the server has stopped, which dropping does not.
+    pub async fn stop(mut self) {
+        self.shutdown.cancel();
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.shutdown.cancel();
+        // The test may spawn the next server right away, which then waits for the turn.
+        *HOLDER.lock().unwrap() = None;
+        let (Some(task), Some(turn)) = (self.task.take(), self.turn.take()) else {
+            return;
+        };
+        // Give the turn to the next server once this one has stopped, or right away if the
+        // runtime of the test is gone, along with the server.
+        if let Ok(handle) = Handle::try_current() {
+            handle.spawn(async move {
+                let _ = task.await;
+                drop(turn);
+            });
+        }
+    }
+}
+
+/// Serve the root on a free port of `127.0.0.1`, without watching the files, until the
+/// returned [`TestServer`] is dropped.
+pub async fn spawn_temp<R: Into<PathBuf>>(root: R) -> Result<TestServer, Box<dyn Error>> {
+    spawn_with(root, |listener| listener).await
+}
+
+/// Like [`spawn_temp`], with the [`Listener`] configured by the callback first.
+///
+/// ```
+/// use live_server::test::spawn_with;
+///
+/// async fn serves_under_the_base() -> Result<(), Box<dyn std::error::Error>> {
+///     let server = spawn_with("./tests/page", |listener| listener.base("/app")).await?;
+///     assert!(server.base_url().ends_with("/app"));
+///     Ok(())
+/// }
+/// ```
+pub async fn spawn_with<R, F>(root: R, configure: F) -> Result<TestServer, Box<dyn Error>>
+where
+    R: Into<PathBuf>,
+    F: FnOnce(Listener) -> Listener,
+{
+    spawn(configure(listen("127.0.0.1:0", root, false).await?)).await
+}
+
+/// Start a listener built by the test, e.g. one watching the files, in turn with the
+/// other test servers. It should listen on `127.0.0.1:0`, so that the tests do not
+/// compete for a port.
+///
+/// ```
+/// use live_server::{listen, test::spawn};
+///
+/// async fn reloads() -> Result<(), Box<dyn std::error::Error>> {
+///     let server = spawn(listen("127.0.0.1:0", "./tests/page", true).await?).await?;
+///     Ok(())
+/// }
+/// ```
+pub async fn spawn(listener: Listener) -> Result<TestServer, Box<dyn Error>> {
+    let turn = take_turn().await?;
+    start(listener, turn)
+}
+
+/// Wait for the turn of the test, giving up if the test holds it itself.
+async fn take_turn() -> Result<Turn, Box<dyn Error>> {
+    // The test may hold the turn in a branch of a `join!` about to end, or for good.
+    let holds_turn = *HOLDER.lock().unwrap() == Some(thread::current().id());
+    let guard = match holds_turn {
+        true => timeout(OWN_TURN_TIMEOUT, TURN.clone().lock_owned())
+            .await
+            .map_err(|_| {
+                "this test still holds a TestServer, which must be stopped or dropped \
+                before spawning another one"
+            })?,
+        false => TURN.clone().lock_owned().await,
+    };
+    *HOLDER.lock().unwrap() = Some(thread::current().id());
+    Ok(Turn { _guard: guard })
+}
+
+fn start(listener: Listener, turn: Turn) -> Result<TestServer, Box<dyn Error>> {
+    let addr = listener.local_addr()?;
+    let url = listener.link()?.trim_end_matches('/').to_string();
+
+    let shutdown = CancellationToken::new();
+    let listener = listener.graceful_shutdown(shutdown.clone().cancelled_owned());
+    let task = tokio::spawn(async move {
+        if let Err(err) = listener.start().await {
+            log::error!("The test server failed: {}", err);
+        }
+    });
+    Ok(TestServer {
+        addr,
+        url,
+        shutdown,
+        task: Some(task),
+        turn: Some(turn),
+    })
+}
@@ -0,0 +1,290 @@
+//! The `--tui` mode of the binary, showing the requests, the connected clients and the
+//! file changes in live panes instead of the log lines.
+
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+    time::{Duration, SystemTime},
+};
+
+use live_server::{Change, RequestSummary};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, Paragraph},
+    DefaultTerminal, Frame,
+};
+use tokio::runtime::Handle;
+use tokio_util::sync::CancellationToken;
+
+/// How many lines of each pane are kept.
+const HISTORY: usize = 200;
+
+/// How often the panes are redrawn when no key is pressed.
+const REFRESH: Duration = Duration::from_millis(250);
+
+/// The state shared between the server's callbacks and the drawing thread.
+#[derive(Clone, Default)]
+pub(crate) struct Tui {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    requests: VecDeque<(String, RequestSummary)>,
+    /// The file changes and the log lines, which cannot be printed under the panes.
+    events: VecDeque<(String, Line<'static>)>,
+    /// Whether the panes are on the screen, otherwise the log lines go to stderr.
+    active: bool,
+}
+
+impl State {
+    fn push_event(&mut self, line: Line<'static>) {
+        push(&mut self.events, (now(), line));
+    }
+}
+
+/// The settings shown in the header.
+pub(crate) struct Header {
+    pub(crate) root: String,
+    pub(crate) links: Vec<String>,
+    pub(crate) watch: bool,
+}
+
+impl Tui {
+    pub(crate) fn request(&self, request: &RequestSummary) {
+        let mut state = self.state.lock().unwrap();
+        push(&mut state.requests, (now(), request.clone()));
+    }
+
+    pub(crate) fn reload(&self, changes: &[Change]) {
+        let mut state = self.state.lock().unwrap();
+        if changes.is_empty() {
+            state.push_event(Line::from("reload".cyan()));
+        }
+        for change in changes {
+            let kind = change.kind().to_string();
+            let kind = match change.kind() {
+                "create" => kind.green(),
+                "remove" => kind.red(),
+                _ => kind.yellow(),
+            };
+            let path = match change.from() {
+                Some(from) => format!("{} -> {}", from, change.path()),
+                None => change.path().to_string(),
+            };
+            state.push_event(Line::from(vec![kind, Span::raw(" "), Span::raw(path)]));
+        }
+    }
+
+    /// The target of the logger, adding the log lines to the events pane while it is shown.
+    pub(crate) fn log_writer(&self) -> LogWriter {
+        LogWriter {
+            state: self.state.clone(),
+        }
+    }
+
+    /// Draw the panes in a thread until `stop` is cancelled, cancelling `quit` when the
+    /// user asks to quit.
+    pub(crate) fn spawn(
+        self,
+        header: Header,
+        quit: CancellationToken,
+        stop: CancellationToken,
+    ) -> io::Result<JoinHandle<()>> {
+        let terminal = ratatui::try_init()?;
+        self.state.lock().unwrap().active = true;
+        let handle = Handle::current();
+
+        Ok(std::thread::spawn(move || {
+            if let Err(err) = self.run(terminal, &header, &handle, &quit, &stop) {
+                log::error!("Failed to draw the terminal UI: {}", err);
+            }
+            ratatui::restore();
+            self.state.lock().unwrap().active = false;
+            quit.cancel();
+        }))
+    }
+
+    fn run(
+        &self,
+        mut terminal: DefaultTerminal,
+        header: &Header,
+        handle: &Handle,
+        quit: &CancellationToken,
+        stop: &CancellationToken,
+    ) -> io::Result<()> {
+        while !stop.is_cancelled() {
+            terminal.draw(|frame| self.draw(frame, header))?;
+            if !event::poll(REFRESH)? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('r') => {
+                    handle.spawn(live_server::reload());
+                }
+                KeyCode::Char('o') => {
+                    if let Some(link) = header.links.first() {
+                        if let Err(err) = open::that(link) {
+                            log::error!("Failed to open {}: {}", link, err);
+                        }
+                    }
+                }
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+                KeyCode::Char('q') | KeyCode::Esc => break,
+                _ => {}
+            }
+        }
+        quit.cancel();
+        Ok(())
+    }
+
+    fn draw(&self, frame: &mut Frame, header: &Header) {
+        let stats = live_server::stats();
+        let state = self.state.lock().unwrap();
+
+        let [top, middle, bottom, footer] = Layout::vertical([
+            Constraint::Length(header.links.len().max(1) as u16 + 3),
+            Constraint::Min(5),
+            Constraint::Length(10),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [requests, clients] =
+            Layout::horizontal([Constraint::Percentage(65), Constraint::Percentage(35)])
+                .areas(middle);
+
+        let watch = match header.watch {
+            true => "on".green(),
+            false => "off".yellow(),
+        };
+        let mut lines = vec![Line::from(vec![
+            "Root  ".dim(),
+            Span::raw(header.root.clone()),
+            "   Watch ".dim(),
+            watch,
+        ])];
+        for link in &header.links {
+            lines.push(Line::from(vec!["Link  ".dim(), link.clone().cyan()]));
+        }
+        lines.push(Line::from(vec![
+            "Served ".dim(),
+            Span::raw(format!(
+                "{} requests, {} bytes, {} reloads, {} errors",
+                stats.requests, stats.bytes, stats.reloads, stats.errors
+            )),
+        ]));
+        let title = concat!(" live-server v", env!("CARGO_PKG_VERSION"), " ");
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title.bold().green())),
+            top,
+        );
+
+        let items = tail(&state.requests, requests.height).map(|(time, request)| {
+            let status = Style::new().fg(match request.status {
+                200..=299 => Color::Green,
+                300..=399 => Color::Cyan,
+                400..=499 => Color::Yellow,
+                _ => Color::Red,
+            });
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{time} ")).dim(),
+                Span::raw(format!("{} ", request.method)).bold(),
+                Span::raw(format!("{} ", request.uri)),
+                Span::styled(request.status.to_string(), status),
+                Span::raw(format!(" {}ms", request.duration.as_millis())).dim(),
+            ]))
+        });
+        frame.render_widget(
+            List::new(items).block(Block::bordered().title(" Requests ")),
+            requests,
+        );
+
+        let items = stats.clients.iter().map(|client| {
+            ListItem::new(Line::from(vec![
+                Span::raw(client.ip.to_string()),
+                Span::raw(format!(
+                    " {} requests, {} bytes",
+                    client.requests, client.bytes
+                ))
+                .dim(),
+            ]))
+        });
+        let title = format!(" Clients, {} connected ", stats.connections);
+        frame.render_widget(
+            List::new(items).block(Block::bordered().title(title)),
+            clients,
+        );
+
+        let items = tail(&state.events, bottom.height).map(|(time, line)| {
+            let mut line = line.clone();
+            line.spans.insert(0, Span::raw(format!("{time} ")).dim());
+            ListItem::new(line)
+        });
+        frame.render_widget(
+            List::new(items).block(Block::bordered().title(" Events ")),
+            bottom,
+        );
+
+        let keys = Line::from(vec![
+            " r".bold(),
+            " reload  ".dim(),
+            "o".bold(),
+            " open browser  ".dim(),
+            "q".bold(),
+            " quit".dim(),
+        ]);
+        frame.render_widget(Paragraph::new(keys), footer);
+    }
+}
+
+/// Writes the log lines to the events pane, or to stderr when it is not shown.
+pub(crate) struct LogWriter {
+    state: Arc<Mutex<State>>,
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.state.lock().unwrap();
+        if !state.active {
+            return io::stderr().write(buf);
+        }
+        for line in String::from_utf8_lossy(buf).lines() {
+            state.push_event(Line::from(line.to_string()).dim());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+fn push<T>(history: &mut VecDeque<T>, item: T) {
+    if history.len() == HISTORY {
+        history.pop_front();
+    }
+    history.push_back(item);
+}
+
+/// The last items fitting in a bordered pane of the height.
+fn tail<T>(history: &VecDeque<T>, height: u16) -> impl Iterator<Item = &T> {
+    let rows = height.saturating_sub(2) as usize;
+    history.iter().skip(history.len().saturating_sub(rows))
+}
+
+/// The current time as `HH:MM:SS` in UTC, like the timestamps of the log lines.
+fn now() -> String {
+    let time = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+    time[11..19].to_string()
+}
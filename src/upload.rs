@@ -0,0 +1,233 @@
+//! Writing files under the root from `PUT` requests and multipart forms, as enabled by
+//! [`Listener::upload`](crate::Listener::upload).
+
+use std::path::{Component, Path, PathBuf};
+
+use axum::{
+    extract::{FromRequest, Multipart, Request},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::{Stream, StreamExt};
+use percent_encoding::percent_decode_str;
+use tokio::io::AsyncWriteExt;
+
+use crate::server::{escapes_root, resolve_mount};
+use crate::util::is_hidden;
+use crate::{OPTIONS, ROOT};
+
+/// Write the request body to the file at the path, replacing it if it exists.
+pub(crate) async fn put(req: Request) -> Response {
+    let Some(limit) = OPTIONS.get().unwrap().upload_limit else {
+        return method_not_allowed().await;
+    };
+    let path = match decode(req.uri().path()).and_then(|path| writable_path(&path)) {
+        Ok(path) => path,
+        Err(status) => return status.into_response(),
+    };
+    if path.is_dir() {
+        return StatusCode::CONFLICT.into_response();
+    }
+    let Some(dir) = path.parent().filter(|dir| dir.is_dir()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let existed = path.exists();
+    let data = req.into_body().into_data_stream();
+    match write(dir, &path, data, limit).await {
+        Ok(()) if existed => StatusCode::NO_CONTENT.into_response(),
+        Ok(()) => StatusCode::CREATED.into_response(),
+        Err(status) => status.into_response(),
+    }
+}
+
+/// Write the files of the multipart form into the directory at the path, next to the
+/// existing ones, e.g. `photo (1).jpg` if there is already a `photo.jpg`.
+pub(crate) async fn post(req: Request) -> Response {
+    let Some(limit) = OPTIONS.get().unwrap().upload_limit else {
+        return method_not_allowed().await;
+    };
+    let dir = match decode(req.uri().path()).and_then(|path| writable_path(&path)) {
+        Ok(dir) if dir.is_dir() => dir,
+        Ok(_) => return StatusCode::NOT_FOUND.into_response(),
+        Err(status) => return status.into_response(),
+    };
+    let from_browser = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+    let location = req.uri().path().to_string();
+
+    let mut multipart = match Multipart::from_request(req, &()).await {
+        Ok(multipart) => multipart,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let mut written = vec![];
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(err) => return err.into_response(),
+        };
+        let Some(name) = field.file_name().and_then(file_name) else {
+            continue;
+        };
+        let path = available_path(&dir, &name);
+        if let Err(status) = write(&dir, &path, field, limit).await {
+            return status.into_response();
+        }
+        written.push(format!(
+            "{}{}",
+            location.trim_end_matches('/'),
+            url_name(&path)
+        ));
+    }
+
+    if from_browser {
+        let base = &OPTIONS.get().unwrap().base;
+        let mut headers = HeaderMap::new();
+        if let Ok(location) = HeaderValue::from_str(&format!("{base}{location}")) {
+            headers.insert(header::LOCATION, location);
+        }
+        return (StatusCode::SEE_OTHER, headers).into_response();
+    }
+    (StatusCode::CREATED, Json(written)).into_response()
+}
+
+/// Refuse the methods the static files are not served with, telling which ones are, with
+/// `PUT` and `POST` only when the uploads are enabled.
+pub(crate) async fn method_not_allowed() -> Response {
+    let allow = match OPTIONS.get().unwrap().upload_limit {
+        Some(_) => "GET, HEAD, PUT, POST",
+        None => "GET, HEAD",
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ALLOW, HeaderValue::from_static(allow));
+    (StatusCode::METHOD_NOT_ALLOWED, headers).into_response()
+}
+
+fn decode(url_path: &str) -> Result<String, StatusCode> {
+    match percent_decode_str(url_path).decode_utf8() {
+        Ok(path) => Ok(path.into_owned()),
+        Err(_) => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
+/// The file system path of the decoded URL path, refusing the paths which would not be
+/// served, such as those going outside of the root or through a dotfile.
+pub(crate) fn writable_path(path: &str) -> Result<PathBuf, StatusCode> {
+    let options = OPTIONS.get().unwrap();
+    let (_, root, rest) = resolve_mount(path);
+    let root = root.as_path();
+    if !options.disk && root == *ROOT.get().unwrap() {
+        return Err(StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    let rest = Path::new(rest.trim_start_matches('/'));
+    for component in rest.components() {
+        match component {
+            Component::Normal(name) if is_hidden(&name.to_string_lossy()) => {
+                if options.deny_dotfiles || !options.show_dotfiles {
+                    log::warn!("Refused to upload to the dotfile {:?}", rest);
+                    return Err(StatusCode::FORBIDDEN);
+                }
+            }
+            Component::Normal(_) => {}
+            _ => return Err(StatusCode::BAD_REQUEST),
+        }
+    }
+
+    let path = root.join(rest);
+    let parent = path.parent().unwrap_or(root);
+    if escapes_root(root, parent) || (path.is_symlink() && escapes_root(root, &path)) {
+        log::warn!("Refused to upload to {:?} outside of the root", path);
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(path)
+}
+
+/// The name to save an uploaded file as, without the directories some browsers send.
+pub(crate) fn file_name(name: &str) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next()?;
+    let name: String = name.chars().filter(|c| !c.is_control()).collect();
+    let options = OPTIONS.get().unwrap();
+    let hidden = is_hidden(&name) && (options.deny_dotfiles || !options.show_dotfiles);
+    (!name.is_empty() && name != "." && name != ".." && !hidden).then_some(name)
+}
+
+/// The path of the name in the directory, numbered if the name is taken.
+fn available_path(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    if !path.exists() {
+        return path;
+    }
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{ext}")),
+        _ => (name, String::new()),
+    };
+    (1..)
+        .map(|n| dir.join(format!("{stem} ({n}){ext}")))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+fn url_name(path: &Path) -> String {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    format!("/{}", crate::util::encode_path(&name))
+}
+
+/// Write the data to a temporary file next to the path, then move it in place, so that
+/// neither the watcher nor the other clients see a partial file.
+async fn write<S, B, E>(dir: &Path, path: &Path, data: S, limit: u64) -> Result<(), StatusCode>
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]>,
+    E: std::fmt::Display,
+{
+    let temp = dir.join(format!(".live-server-upload-{:016x}", fastrand::u64(..)));
+    let mut data = std::pin::pin!(data);
+    let result = async {
+        let mut file = tokio::fs::File::create(&temp).await.map_err(internal)?;
+        let mut size = 0;
+        while let Some(chunk) = data.next().await {
+            let chunk = chunk.map_err(|err| {
+                log::warn!("Failed to receive the upload of {:?}: {}", path, err);
+                StatusCode::BAD_REQUEST
+            })?;
+            size += chunk.as_ref().len() as u64;
+            if size > limit {
+                log::warn!("Refused to upload {:?} larger than {} bytes", path, limit);
+                return Err(StatusCode::PAYLOAD_TOO_LARGE);
+            }
+            file.write_all(chunk.as_ref()).await.map_err(internal)?;
+        }
+        file.flush().await.map_err(internal)?;
+        tokio::fs::rename(&temp, path).await.map_err(internal)?;
+        log::info!("[UPLOAD] Wrote {:?} ({} bytes)", path, size);
+        Ok(())
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&temp).await;
+    }
+    result
+}
+
+fn internal(err: std::io::Error) -> StatusCode {
+    log::error!("Failed to save the upload: {}", err);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+/// The form of the directory listing for uploading files into the directory.
+#[cfg(feature = "listing")]
+pub(crate) fn form() -> &'static str {
+    match OPTIONS.get().unwrap().upload_limit {
+        Some(_) => {
+            r#"<form id="upload" method="post" enctype="multipart/form-data"><input type="file" name="file" multiple required /><button>{{ t_upload }}</button></form>"#
+        }
+        None => "",
+    }
+}
@@ -0,0 +1,48 @@
+use std::path::{Component, Path};
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+/// Whether the file is a dotfile, such as `.env` or `.git`. `.well-known` is not
+/// considered hidden, since it is meant to be served.
+pub(crate) fn is_hidden(name: &str) -> bool {
+    name.starts_with('.') && name != ".well-known"
+}
+
+/// Whether the path, relative to the root, goes through a dotfile.
+pub(crate) fn has_hidden_component(root: &Path, path: &Path) -> bool {
+    path.strip_prefix(root).is_ok_and(|path| {
+        path.components().any(|component| match component {
+            Component::Normal(name) => is_hidden(&name.to_string_lossy()),
+            _ => false,
+        })
+    })
+}
+
+/// Characters to encode in a URL path segment, so that names with spaces, `#`, `?`
+/// or non-ASCII characters produce working links.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// Percent-encode each segment of the URL path, keeping the slashes.
+pub(crate) fn encode_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| utf8_percent_encode(segment, PATH_SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub(crate) fn render<S: AsRef<str>>(template: String, var_name: &str, value: S) -> String {
+    template.replace(&format!("{{{{ {} }}}}", var_name), value.as_ref())
+}
+
+pub(crate) fn escape_html<S: AsRef<str>>(input: S) -> String {
+    input
+        .as_ref()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
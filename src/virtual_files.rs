@@ -0,0 +1,89 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    watcher::{broadcast, Change},
+    TX,
+};
+
+static VIRTUAL_FILES: Mutex<BTreeMap<String, Content>> = Mutex::new(BTreeMap::new());
+
+pub(crate) type Generator = Arc<dyn Fn() -> Vec<u8> + Send + Sync>;
+
+#[derive(Clone)]
+pub(crate) enum Content {
+    Bytes(Arc<[u8]>),
+    Generator(Generator),
+}
+
+/// Make sure the URL path starts with a slash, as the request paths do.
+fn normalize(path: &str) -> String {
+    format!("/{}", path.trim_start_matches('/'))
+}
+
+pub(crate) fn insert_bytes(path: &str, content: Vec<u8>) {
+    insert(path, Content::Bytes(content.into()));
+}
+
+pub(crate) fn insert(path: &str, content: Content) {
+    let mut files = VIRTUAL_FILES.lock().unwrap();
+    files.insert(normalize(path), content);
+}
+
+/// Forget the virtual files, when the server stops.
+pub(crate) fn clear() {
+    VIRTUAL_FILES.lock().unwrap().clear();
+}
+
+/// Get the content of the virtual file at the URL path, generating it if needed.
+pub(crate) fn get(path: &str) -> Option<Vec<u8>> {
+    // Do not hold the lock while generating, in case the generator is slow.
+    let content = VIRTUAL_FILES.lock().unwrap().get(path).cloned()?;
+    Some(match content {
+        Content::Bytes(bytes) => bytes.to_vec(),
+        Content::Generator(generator) => generator(),
+    })
+}
+
+/// Tell the clients that the virtual file changed, if the server is running.
+async fn notify(kind: &'static str, path: &str) {
+    if TX.get().is_some() {
+        broadcast(&[Change::at_url(kind, &normalize(path))]).await;
+    }
+}
+
+/// Add a virtual file, or replace its content, and reload the pages.
+///
+/// Virtual files are served at their URL path before the files on the disk, so they can
+/// be generated in memory, e.g. by a static site generator.
+///
+/// ```
+/// async fn rebuild() {
+///     live_server::set_virtual_file("/index.html", "<h1>Rebuilt</h1>").await;
+/// }
+/// ```
+pub async fn set_virtual_file<P: AsRef<str>, C: Into<Vec<u8>>>(path: P, content: C) {
+    insert_bytes(path.as_ref(), content.into());
+    notify("modify", path.as_ref()).await;
+}
+
+/// Remove a virtual file, and reload the pages. The file on the disk at the same
+/// path, if any, is served again.
+///
+/// ```
+/// async fn clean() {
+///     live_server::remove_virtual_file("/index.html").await;
+/// }
+/// ```
+pub async fn remove_virtual_file<P: AsRef<str>>(path: P) {
+    let removed = VIRTUAL_FILES
+        .lock()
+        .unwrap()
+        .remove(&normalize(path.as_ref()))
+        .is_some();
+    if removed {
+        notify("remove", path.as_ref()).await;
+    }
+}
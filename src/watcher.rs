@@ -1,119 +1,146 @@
-use std::{
-    path::{Path, PathBuf},
-    time::Duration,
-};
-
-use notify::{Error, RecommendedWatcher, RecursiveMode, Watcher as NotifyWatcher};
-use notify_debouncer_full::{
-    new_debouncer, DebounceEventResult, DebouncedEvent, Debouncer, FileIdMap,
-};
-use tokio::{
-    runtime::Handle,
-    sync::mpsc::{channel, Receiver},
-};
-
-use crate::TX;
-
-async fn broadcast() {
-    let tx = TX.get().unwrap();
-    let _ = tx.send(());
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use serde::Serialize;
+
+use crate::{protocol::Message, server::send_message, stats::STATS, OPTIONS, TX};
+
+/// Watching the files with `notify`, which the `watch` feature can leave out for the
+/// embedders which only reload the pages themselves, e.g. with `set_virtual_file`.
+#[cfg(feature = "watch")]
+mod fs;
+
+#[cfg(feature = "watch")]
+pub(crate) use fs::{create_watcher, take_exec_failure, watch, Filter};
+
+/// A changed file, as sent to the clients in the reload message.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    /// The URL path if the file is under the root, or the file system path otherwise.
+    path: String,
+    /// `create`, `modify`, `rename` or `remove`.
+    kind: &'static str,
+    /// The previous path of a renamed file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from: Option<String>,
+    #[serde(skip)]
+    file: PathBuf,
+}
+
+impl Change {
+    /// A change of a file served at the URL path, which is not on the disk.
+    pub(crate) fn at_url(kind: &'static str, path: &str) -> Self {
+        Change {
+            path: path.to_string(),
+            kind,
+            from: None,
+            file: PathBuf::from(path),
+        }
+    }
+
+    /// The URL path if the file is under the root, or the file system path otherwise.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// `create`, `modify`, `rename` or `remove`.
+    pub fn kind(&self) -> &str {
+        self.kind
+    }
+
+    /// The previous path of a renamed file.
+    pub fn from(&self) -> Option<&str> {
+        self.from.as_deref()
+    }
 }
 
-pub struct Watcher {
-    debouncer: Debouncer<RecommendedWatcher, FileIdMap>,
-    rx: Receiver<Result<Vec<DebouncedEvent>, Vec<notify::Error>>>,
+/// A callback of [`Listener::on_reload`](crate::Listener::on_reload).
+#[derive(Clone)]
+pub(crate) struct ReloadHook(pub(crate) Callback);
+
+type Callback = Arc<dyn Fn(&[Change]) + Send + Sync>;
+
+impl std::fmt::Debug for ReloadHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ReloadHook")
+    }
 }
 
-pub(crate) async fn create_watcher() -> Result<Watcher, String> {
-    let rt = Handle::current();
-    let (tx, rx) = channel::<Result<Vec<DebouncedEvent>, Vec<Error>>>(16);
-    new_debouncer(
-        Duration::from_millis(200),
-        None,
-        move |result: DebounceEventResult| {
-            let tx = tx.clone();
-            rt.spawn(async move {
-                if let Err(err) = tx.send(result).await {
-                    log::error!("Failed to send event result: {}", err);
-                }
-            });
-        },
-    )
-    .map(|debouncer| Watcher { debouncer, rx })
-    .map_err(|e| e.to_string())
+/// Reload the pages connected to the running server, e.g. after rebuilding the site
+/// outside of the watched files. Does nothing if the server is not running.
+///
+/// ```
+/// async fn rebuilt() {
+///     live_server::reload().await;
+/// }
+/// ```
+pub async fn reload() {
+    if TX.get().is_some() {
+        broadcast(&[]).await;
+    }
 }
 
-pub async fn watch(root_path: PathBuf, mut watcher: Watcher) {
-    watcher
-        .debouncer
-        .watcher()
-        .watch(&root_path, RecursiveMode::Recursive)
-        .unwrap();
-    watcher
-        .debouncer
-        .cache()
-        .add_root(&root_path, RecursiveMode::Recursive);
-
-    while let Some(result) = watcher.rx.recv().await {
-        let mut files_changed = false;
-        match result {
-            Ok(events) => {
-                for e in events {
-                    use notify::EventKind::*;
-                    match e.event.kind {
-                        Create(_) => {
-                            let path = e.event.paths[0].to_str().unwrap();
-                            log::debug!("[CREATE] {}", path);
-                            files_changed = true;
-                        }
-                        Modify(kind) => {
-                            use notify::event::ModifyKind::*;
-                            match kind {
-                                Name(kind) => {
-                                    use notify::event::RenameMode::*;
-                                    if let Both = kind {
-                                        let source_name = &e.event.paths[0];
-                                        let target_name = &e.event.paths[1];
-                                        log::debug!(
-                                            "[RENAME] {} -> {}",
-                                            strip_prefix(source_name, &root_path),
-                                            strip_prefix(target_name, &root_path)
-                                        );
-                                        files_changed = true;
-                                    }
-                                }
-                                _ => {
-                                    let paths = e.event.paths[0].to_str().unwrap();
-                                    log::debug!("[UPDATE] {}", paths);
-                                    files_changed = true;
-                                }
-                            }
-                        }
-                        Remove(_) => {
-                            let paths = e.event.paths[0].to_str().unwrap();
-                            log::debug!("[REMOVE] {}", paths);
-                            files_changed = true;
-                        }
-                        _ => {}
-                    }
-                }
-            }
-            Err(errors) => {
-                for err in errors {
-                    log::error!("{}", err);
-                }
-            }
-        }
-        if files_changed {
-            broadcast().await;
+/// Tell the clients to reload, or only to update the stylesheets if nothing else changed.
+pub(crate) async fn broadcast(changes: &[Change]) {
+    let css_only = !changes.is_empty()
+        && changes.iter().all(|change| {
+            matches!(change.kind, "create" | "modify")
+                && change.path.starts_with('/')
+                && change.file.extension().is_some_and(|ext| ext == "css")
+        });
+    let message = if css_only {
+        Message::CssUpdate {
+            paths: changes.iter().map(|change| change.path.as_str()).collect(),
         }
+    } else {
+        Message::Reload { changes }
+    };
+
+    #[cfg(feature = "mmap")]
+    crate::mmap::forget(changes.iter().map(|change| change.file.as_path()));
+    send_message(message.to_json());
+    STATS.add_reload();
+
+    let options = OPTIONS.get();
+    let hooks = options
+        .as_ref()
+        .map(|options| options.on_reload.as_slice())
+        .unwrap_or_default();
+    for ReloadHook(hook) in hooks {
+        hook(changes);
     }
 }
 
-fn strip_prefix(path: &Path, prefix: &PathBuf) -> String {
-    path.strip_prefix(prefix)
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .to_string()
+/// The options of the watcher, configured through the [`Listener`](crate::Listener).
+pub(crate) struct WatchOptions {
+    pub(crate) ignore: Vec<String>,
+    pub(crate) gitignore: bool,
+    pub(crate) extensions: Vec<String>,
+    pub(crate) debounce: Duration,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) extra_paths: Vec<PathBuf>,
+    pub(crate) exec: Option<String>,
+    pub(crate) quiet_period: Option<Duration>,
+    pub(crate) reload_marker: Option<PathBuf>,
+    /// The file served as the root, whose changes are the only ones triggering a reload
+    /// unless `watch_siblings` is set.
+    pub(crate) file: Option<PathBuf>,
+    pub(crate) watch_siblings: bool,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        WatchOptions {
+            ignore: Vec::new(),
+            gitignore: false,
+            extensions: Vec::new(),
+            debounce: Duration::from_millis(200),
+            max_depth: None,
+            extra_paths: Vec::new(),
+            exec: None,
+            quiet_period: None,
+            reload_marker: None,
+            file: None,
+            watch_siblings: false,
+        }
+    }
 }
@@ -0,0 +1,630 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Mutex,
+    time::Duration,
+};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match, WalkBuilder,
+};
+use notify::{
+    Error, ErrorKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher as NotifyWatcher,
+};
+use notify_debouncer_full::{
+    new_debouncer, new_debouncer_opt, DebounceEventHandler, DebounceEventResult, DebouncedEvent,
+    Debouncer, FileIdMap,
+};
+use tokio::{
+    runtime::Handle,
+    sync::mpsc::{channel, Receiver, Sender},
+    time::timeout,
+};
+use walkdir::WalkDir;
+
+use super::{broadcast, Change, WatchOptions};
+use crate::{
+    health, path_to_string_but_readable, protocol::Message, server::send_message, stats::STATS,
+    ListenError, OPTIONS,
+};
+
+impl Change {
+    fn new(root_path: &Path, kind: &'static str, file: &Path) -> Self {
+        Change {
+            path: url_path(root_path, file),
+            kind,
+            from: None,
+            file: file.to_path_buf(),
+        }
+    }
+}
+
+fn url_path(root_path: &Path, file: &Path) -> String {
+    let options = OPTIONS.get();
+    let base = options.as_ref().map_or("", |options| options.base.as_str());
+    if let Ok(path) = file.strip_prefix(root_path) {
+        return format!("{}/{}", base, path_to_string_but_readable(path));
+    }
+    let mounts = options.as_ref().map(|options| options.mounts.as_slice());
+    for (prefix, dir) in mounts.unwrap_or_default() {
+        if let Ok(path) = file.strip_prefix(dir) {
+            return format!("{}{}/{}", base, prefix, path_to_string_but_readable(path));
+        }
+    }
+    path_to_string_but_readable(file)
+}
+
+/// Show the error as an overlay on the pages, until the next reload.
+pub(crate) fn broadcast_error(message: &str) {
+    send_message(Message::Error { message }.to_json());
+}
+
+type EventResult = Result<Vec<DebouncedEvent>, Vec<Error>>;
+
+/// The failure of the last run of the `exec` command, if it failed, which `start`
+/// returns once the server stops.
+static EXEC_FAILURE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Take the failure of the last run of the `exec` command, if it failed.
+pub(crate) fn take_exec_failure() -> Option<String> {
+    EXEC_FAILURE.lock().unwrap().take()
+}
+
+/// How often the files are polled when the native backend is unavailable.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+enum Backend {
+    Native(Debouncer<RecommendedWatcher, FileIdMap>),
+    Poll(Debouncer<PollWatcher, FileIdMap>),
+}
+
+pub struct Watcher {
+    backend: Backend,
+    tx: Sender<EventResult>,
+    rx: Receiver<EventResult>,
+    debounce: Duration,
+    max_depth: Option<usize>,
+    extra_paths: Vec<PathBuf>,
+    exec: Option<String>,
+    quiet_period: Option<Duration>,
+    reload_marker: Option<PathBuf>,
+}
+
+impl Watcher {
+    fn watch_dir(&mut self, path: &Path, mode: RecursiveMode) -> Result<(), Error> {
+        match &mut self.backend {
+            Backend::Native(debouncer) => {
+                debouncer.watcher().watch(path, mode)?;
+                debouncer.cache().add_root(path, mode);
+            }
+            Backend::Poll(debouncer) => {
+                debouncer.watcher().watch(path, mode)?;
+                debouncer.cache().add_root(path, mode);
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop watching the directory, if it still is, e.g. before watching it again
+    /// once it has been recreated.
+    fn unwatch_dir(&mut self, path: &Path) {
+        match &mut self.backend {
+            Backend::Native(debouncer) => {
+                let _ = debouncer.watcher().unwatch(path);
+                debouncer.cache().remove_root(path);
+            }
+            Backend::Poll(debouncer) => {
+                let _ = debouncer.watcher().unwatch(path);
+                debouncer.cache().remove_root(path);
+            }
+        }
+    }
+
+    /// Watch the directory, which is `depth` levels below the root, and its
+    /// subdirectories up to the maximum depth.
+    fn add_dir(&mut self, path: &Path, depth: usize) -> Result<(), Error> {
+        let Some(max_depth) = self.max_depth else {
+            return self.watch_dir(path, RecursiveMode::Recursive);
+        };
+
+        let dirs = WalkDir::new(path)
+            .max_depth(max_depth.saturating_sub(depth))
+            .into_iter()
+            .filter_entry(|entry| entry.file_type().is_dir());
+        for dir in dirs {
+            let dir = match dir {
+                Ok(dir) => dir,
+                Err(err) => {
+                    log::warn!("Failed to read directory: {}", err);
+                    continue;
+                }
+            };
+            self.watch_dir(dir.path(), RecursiveMode::NonRecursive)?;
+        }
+
+        Ok(())
+    }
+
+    /// Replace the native backend with polling, which has no limit on the number of
+    /// watched files but is slower to notice the changes.
+    fn fall_back_to_polling(&mut self) -> Result<(), Error> {
+        let config = notify::Config::default().with_poll_interval(POLL_INTERVAL);
+        let debouncer = new_debouncer_opt(
+            self.debounce,
+            None,
+            event_handler(self.tx.clone()),
+            FileIdMap::new(),
+            config,
+        )?;
+        self.backend = Backend::Poll(debouncer);
+        Ok(())
+    }
+}
+
+fn event_handler(tx: Sender<EventResult>) -> impl DebounceEventHandler {
+    let rt = Handle::current();
+    move |result: DebounceEventResult| {
+        let tx = tx.clone();
+        rt.spawn(async move {
+            if let Err(err) = tx.send(result).await {
+                log::error!("Failed to send event result: {}", err);
+            }
+        });
+    }
+}
+
+pub(crate) async fn create_watcher(options: &WatchOptions) -> Result<Watcher, String> {
+    let (tx, rx) = channel::<EventResult>(16);
+    new_debouncer(options.debounce, None, event_handler(tx.clone()))
+        .map(|debouncer| Watcher {
+            backend: Backend::Native(debouncer),
+            tx,
+            rx,
+            debounce: options.debounce,
+            max_depth: options.max_depth,
+            extra_paths: options.extra_paths.clone(),
+            exec: options.exec.clone(),
+            quiet_period: options.quiet_period,
+            reload_marker: options.reload_marker.clone(),
+        })
+        .map_err(|e| e.to_string())
+}
+
+/// Decides which changed paths should not trigger a reload.
+pub(crate) struct Filter {
+    ignore: GlobSet,
+    gitignore: Option<Vec<Gitignore>>,
+    extensions: Vec<String>,
+    only_file: Option<PathBuf>,
+}
+
+impl Filter {
+    pub(crate) async fn new(root_path: &Path, options: &WatchOptions) -> Result<Self, String> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &options.ignore {
+            let glob = Glob::new(pattern)
+                .map_err(|err| format!("Invalid ignore pattern {:?}: {}", pattern, err))?;
+            builder.add(glob);
+        }
+        let ignore = builder.build().map_err(|e| e.to_string())?;
+
+        let gitignore = match options.gitignore {
+            true => Some(load_gitignores(root_path).await),
+            false => None,
+        };
+
+        let extensions = options
+            .extensions
+            .iter()
+            .map(|ext| ext.trim_start_matches('.').to_lowercase())
+            .collect();
+
+        let only_file = match options.watch_siblings {
+            true => None,
+            false => options.file.clone(),
+        };
+
+        Ok(Filter {
+            ignore,
+            gitignore,
+            extensions,
+            only_file,
+        })
+    }
+
+    fn is_ignored(&self, root_path: &Path, path: &Path) -> bool {
+        if let Some(file) = &self.only_file {
+            if path.starts_with(root_path) && path != file {
+                return true;
+            }
+        }
+
+        if !self.extensions.is_empty() {
+            let ext = path
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase());
+            if !ext.is_some_and(|ext| self.extensions.contains(&ext)) {
+                return true;
+            }
+        }
+
+        if let Some(gitignores) = &self.gitignore {
+            if is_gitignored(gitignores, root_path, path) {
+                return true;
+            }
+        }
+
+        let path = path.strip_prefix(root_path).unwrap_or(path);
+        self.ignore.is_match(path)
+    }
+
+    /// Reload the ignore files if one of them has changed.
+    async fn refresh(&mut self, root_path: &Path, paths: &[PathBuf]) {
+        if self.gitignore.is_some() && paths.iter().any(|path| is_ignore_file(path)) {
+            log::debug!("Reloading ignore files");
+            self.gitignore = Some(load_gitignores(root_path).await);
+        }
+    }
+}
+
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+fn is_ignore_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| IGNORE_FILES.iter().any(|file| name == *file))
+}
+
+/// Collect the ignore files under the root, on a blocking thread since it walks the
+/// whole tree.
+async fn load_gitignores(root_path: &Path) -> Vec<Gitignore> {
+    let root_path = root_path.to_path_buf();
+    tokio::task::spawn_blocking(move || walk_gitignores(&root_path))
+        .await
+        .unwrap_or_else(|err| {
+            log::error!("Failed to load the ignore files: {}", err);
+            vec![]
+        })
+}
+
+/// Collect the ignore files under the root, deepest directories first so that
+/// they take precedence over their parents.
+fn walk_gitignores(root_path: &Path) -> Vec<Gitignore> {
+    let mut gitignores = vec![];
+
+    for entry in WalkBuilder::new(root_path).hidden(false).build().flatten() {
+        if !entry.file_type().is_some_and(|t| t.is_dir()) {
+            continue;
+        }
+        let dir = entry.path();
+        let mut builder = GitignoreBuilder::new(dir);
+        let mut found = false;
+        for file in IGNORE_FILES {
+            let file = dir.join(file);
+            if file.is_file() {
+                if let Some(err) = builder.add(&file) {
+                    log::warn!("Failed to parse {:?}: {}", file, err);
+                }
+                found = true;
+            }
+        }
+        if found {
+            match builder.build() {
+                Ok(gitignore) => gitignores.push(gitignore),
+                Err(err) => log::warn!("Failed to load ignore files in {:?}: {}", dir, err),
+            }
+        }
+    }
+
+    gitignores.sort_by_key(|gitignore| std::cmp::Reverse(gitignore.path().components().count()));
+    gitignores
+}
+
+fn is_gitignored(gitignores: &[Gitignore], root_path: &Path, path: &Path) -> bool {
+    if path
+        .strip_prefix(root_path)
+        .is_ok_and(|path| path.starts_with(".git"))
+    {
+        return true;
+    }
+
+    let is_dir = path.is_dir();
+    for gitignore in gitignores {
+        if !path.starts_with(gitignore.path()) {
+            continue;
+        }
+        match gitignore.matched_path_or_any_parents(path, is_dir) {
+            Match::Ignore(_) => return true,
+            Match::Whitelist(_) => return false,
+            Match::None => {}
+        }
+    }
+
+    false
+}
+
+pub async fn watch(
+    root_path: PathBuf,
+    mut watcher: Watcher,
+    mut filter: Filter,
+) -> Result<(), ListenError> {
+    let mut roots = vec![root_path.clone()];
+    roots.extend(watcher.extra_paths.clone());
+    // The roots which were removed, e.g. by `rm -rf dist` before a build, or which do
+    // not exist yet, and which are watched once they are created.
+    let mut missing: Vec<_> = roots
+        .iter()
+        .filter(|root| !root.exists())
+        .cloned()
+        .collect();
+    let existing: Vec<_> = roots.iter().filter(|root| root.exists()).cloned().collect();
+    if let Err(err) = add_roots(&mut watcher, &existing) {
+        if !matches!(err.kind, ErrorKind::MaxFilesWatch) {
+            let err_msg = format!("Failed to watch: {}", err);
+            log::error!("{}", err_msg);
+            return Err(ListenError::Watcher(err_msg));
+        }
+
+        let reason = "The inotify watch limit is exhausted".to_string();
+        log::error!(
+            "{}, falling back to polling which is slower. \
+            Consider raising fs.inotify.max_user_watches or using --watch-depth.",
+            reason
+        );
+        let fallback = watcher
+            .fall_back_to_polling()
+            .and_then(|_| add_roots(&mut watcher, &existing));
+        if let Err(err) = fallback {
+            let err_msg = format!("Failed to watch with polling: {}", err);
+            log::error!("{}", err_msg);
+            return Err(ListenError::Watcher(err_msg));
+        }
+        health::set_watcher_backend("poll", Some(reason));
+    } else {
+        health::set_watcher_backend("native", None);
+    }
+
+    loop {
+        let mut changes = tokio::select! {
+            result = watcher.rx.recv() => match result {
+                Some(result) => handle_events(&mut watcher, &mut filter, &roots, result).await,
+                None => break,
+            },
+            _ = tokio::time::sleep(POLL_INTERVAL), if !missing.is_empty() => {
+                rewatch_roots(&mut watcher, &root_path, &mut missing)
+            }
+        };
+        for root in &roots {
+            if !root.exists() && !missing.contains(root) {
+                log::warn!("{:?} was removed, waiting for it to be recreated", root);
+                missing.push(root.clone());
+            }
+        }
+        if changes.is_empty() {
+            continue;
+        }
+
+        // Keep collecting the changes until the files stop changing.
+        if let Some(quiet_period) = watcher.quiet_period {
+            while let Ok(Some(result)) = timeout(quiet_period, watcher.rx.recv()).await {
+                for change in handle_events(&mut watcher, &mut filter, &roots, result).await {
+                    if !changes.contains(&change) {
+                        changes.push(change);
+                    }
+                }
+            }
+        }
+
+        if let Some(marker) = &watcher.reload_marker {
+            if !changes.iter().any(|change| &change.file == marker) {
+                log::debug!("Waiting for {:?} to change before reloading", marker);
+                continue;
+            }
+        }
+
+        match &watcher.exec {
+            Some(command) => {
+                if run_command(command).await {
+                    broadcast(&changes).await;
+                }
+                // Skip the changes made by the command itself, e.g. the build output.
+                tokio::time::sleep(watcher.debounce * 2).await;
+                while watcher.rx.try_recv().is_ok() {}
+            }
+            None => broadcast(&changes).await,
+        }
+    }
+
+    Ok(())
+}
+
+/// Log the events and return the changed paths which are not ignored.
+async fn handle_events(
+    watcher: &mut Watcher,
+    filter: &mut Filter,
+    roots: &[PathBuf],
+    result: EventResult,
+) -> Vec<Change> {
+    let root_path = &roots[0];
+    let mut changes = vec![];
+    match result {
+        Ok(events) => {
+            let received = events.len();
+            let mut ignored = 0;
+            for e in events {
+                filter.refresh(root_path, &e.event.paths).await;
+                let is_marker = |path: &PathBuf| Some(path) == watcher.reload_marker.as_ref();
+                if e.event
+                    .paths
+                    .iter()
+                    .all(|path| filter.is_ignored(root_path, path) && !is_marker(path))
+                {
+                    ignored += 1;
+                    continue;
+                }
+                use notify::EventKind::*;
+                match e.event.kind {
+                    Create(_) => {
+                        let path = &e.event.paths[0];
+                        log::debug!("[CREATE] {}", path.to_str().unwrap());
+                        changes.push(Change::new(root_path, "create", path));
+
+                        // Directories created later are not covered by the non-recursive watches.
+                        if let Some(max_depth) = watcher.max_depth {
+                            let depth = roots
+                                .iter()
+                                .filter_map(|root| path.strip_prefix(root).ok())
+                                .map(|path| path.components().count())
+                                .min()
+                                .unwrap_or(0);
+                            if depth <= max_depth && path.is_dir() {
+                                if let Err(err) = watcher.add_dir(path, depth) {
+                                    log::error!("Failed to watch {:?}: {}", path, err);
+                                }
+                            }
+                        }
+                    }
+                    Modify(kind) => {
+                        use notify::event::ModifyKind::*;
+                        match kind {
+                            Name(kind) => {
+                                use notify::event::RenameMode::*;
+                                if let Both = kind {
+                                    let source_name = &e.event.paths[0];
+                                    let target_name = &e.event.paths[1];
+                                    log::debug!(
+                                        "[RENAME] {} -> {}",
+                                        strip_prefix(source_name, root_path),
+                                        strip_prefix(target_name, root_path)
+                                    );
+                                    let mut change = Change::new(root_path, "rename", target_name);
+                                    change.from = Some(url_path(root_path, source_name));
+                                    changes.push(change);
+                                }
+                            }
+                            _ => {
+                                let path = &e.event.paths[0];
+                                log::debug!("[UPDATE] {}", path.to_str().unwrap());
+                                changes.push(Change::new(root_path, "modify", path));
+                            }
+                        }
+                    }
+                    Remove(_) => {
+                        let path = &e.event.paths[0];
+                        log::debug!("[REMOVE] {}", path.to_str().unwrap());
+                        changes.push(Change::new(root_path, "remove", path));
+                    }
+                    _ => {}
+                }
+            }
+            health::record_events(received, ignored);
+            if let Some(change) = changes.last() {
+                health::record_change(&change.path, change.kind);
+            }
+        }
+        Err(errors) => {
+            for err in errors {
+                log::error!("{}", err);
+                health::record_error(err.to_string());
+                broadcast_error(&format!("Watcher error: {}", err));
+                STATS.add_error();
+            }
+        }
+    }
+
+    changes
+}
+
+/// Run the command in the shell, returning whether it succeeded. The watching goes on
+/// when it cannot even be started, e.g. without a shell, as when it fails.
+async fn run_command(command: &str) -> bool {
+    log::info!("[EXEC] {}", command);
+
+    #[cfg(windows)]
+    let (shell, flag) = ("cmd", "/C");
+    #[cfg(not(windows))]
+    let (shell, flag) = ("sh", "-c");
+
+    // Capture stderr to show it on the pages as well.
+    let output = match tokio::process::Command::new(shell)
+        .arg(flag)
+        .arg(command)
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::piped())
+        .output()
+        .await
+    {
+        Ok(output) => output,
+        Err(err) => {
+            let err_msg = format!("Failed to run {:?}: {}", command, err);
+            log::error!("[EXEC] {}, skipping reload", err_msg);
+            broadcast_error(&err_msg);
+            STATS.add_error();
+            *EXEC_FAILURE.lock().unwrap() = Some(err_msg);
+            return false;
+        }
+    };
+    let status = output.status;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    // Through the logger, which may write to the terminal UI or the log file.
+    if status.success() && !stderr.trim().is_empty() {
+        log::info!("[EXEC] {}", stderr.trim_end());
+    }
+    if !status.success() {
+        log::error!(
+            "[EXEC] {:?} failed with {}, skipping reload\n{}",
+            command,
+            status,
+            stderr.trim_end()
+        );
+        broadcast_error(&format!("{} failed with {}\n\n{}", command, status, stderr));
+        STATS.add_error();
+    }
+    *EXEC_FAILURE.lock().unwrap() = match status.success() {
+        true => None,
+        false => Some(format!("{:?} failed with {}", command, status)),
+    };
+
+    status.success()
+}
+
+fn add_roots(watcher: &mut Watcher, roots: &[PathBuf]) -> Result<(), Error> {
+    for path in roots {
+        watcher
+            .add_dir(path, 0)
+            .map_err(|err| err.add_path(path.clone()))?;
+    }
+    Ok(())
+}
+
+/// Watch the missing roots which have been created, returning the changes to reload for.
+fn rewatch_roots(
+    watcher: &mut Watcher,
+    root_path: &Path,
+    missing: &mut Vec<PathBuf>,
+) -> Vec<Change> {
+    let mut changes = vec![];
+    missing.retain(|path| {
+        if !path.is_dir() {
+            return true;
+        }
+        watcher.unwatch_dir(path);
+        if let Err(err) = watcher.add_dir(path, 0) {
+            log::error!("Failed to watch {:?} again: {}", path, err);
+            return true;
+        }
+        log::info!("{:?} was created, watching it", path);
+        changes.push(Change::new(root_path, "create", path));
+        false
+    });
+    changes
+}
+
+fn strip_prefix(path: &Path, prefix: &PathBuf) -> String {
+    path.strip_prefix(prefix)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
+}
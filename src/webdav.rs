@@ -0,0 +1,178 @@
+//! Serving the root over WebDAV under `/_live-server/webdav`, for mounting it as a network
+//! drive, as enabled by [`Listener::webdav`](crate::Listener::webdav).
+
+use std::path::{Component, Path};
+
+use axum::{
+    body::Body,
+    extract::{OriginalUri, Request},
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use dav_server::{fakels::FakeLs, localfs::LocalFs, DavHandler};
+use percent_encoding::percent_decode_str;
+
+use crate::admin::{constant_time_eq, ADMIN_TOKEN};
+use crate::server::escapes_root;
+use crate::util::is_hidden;
+use crate::{OPTIONS, ROOT};
+
+const PREFIX: &str = "/_live-server/webdav";
+
+pub(crate) async fn handle(mut req: Request) -> Response {
+    let options = OPTIONS.get().unwrap();
+    if !options.webdav {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    if !options.disk {
+        return StatusCode::METHOD_NOT_ALLOWED.into_response();
+    }
+    let reads = matches!(
+        req.method().as_str(),
+        "GET" | "HEAD" | "OPTIONS" | "PROPFIND"
+    );
+    if !reads && options.upload_limit.is_none() && !is_authorized(req.headers()) {
+        return match ADMIN_TOKEN.get() {
+            Some(_) => {
+                let mut headers = HeaderMap::new();
+                let challenge = HeaderValue::from_static("Basic realm=\"live-server\"");
+                headers.insert(header::WWW_AUTHENTICATE, challenge);
+                (StatusCode::UNAUTHORIZED, headers).into_response()
+            }
+            None => StatusCode::FORBIDDEN.into_response(),
+        };
+    }
+    // The path without the base path, but with the prefix the nested router took off.
+    let Some(OriginalUri(uri)) = req.extensions().get::<OriginalUri>().cloned() else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    if let Err(status) = check_path(uri.path()) {
+        return status.into_response();
+    }
+
+    // The links in the responses and the `Destination` headers of the clients have the
+    // base path, which was stripped before routing.
+    let base = &options.base;
+    if matches!(req.method().as_str(), "MOVE" | "COPY") {
+        if let Err(status) = check_destination(req.headers(), base) {
+            return status.into_response();
+        }
+    }
+    let path = match uri.path() {
+        PREFIX => format!("{base}{PREFIX}/"),
+        path => format!("{base}{path}"),
+    };
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path,
+    };
+    match path_and_query.parse::<Uri>() {
+        Ok(uri) => *req.uri_mut() = uri,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    }
+
+    // The lock system is a fake one, which is enough for the clients of macOS and Windows
+    // to mount the drive read-write.
+    let handler = DavHandler::builder()
+        .filesystem(LocalFs::new(
+            ROOT.get().unwrap().as_path(),
+            true,
+            false,
+            false,
+        ))
+        .locksystem(FakeLs::new())
+        .strip_prefix(format!("{base}{PREFIX}"))
+        .hide_symlinks(!options.follow_symlinks)
+        .build_handler();
+    handler.handle(req).await.map(Body::new)
+}
+
+/// Whether the request has the admin token, as a bearer token or as the password of the
+/// basic authentication which the WebDAV clients ask for, with any user name.
+fn is_authorized(headers: &HeaderMap) -> bool {
+    let Some(token) = ADMIN_TOKEN.get() else {
+        return false;
+    };
+    let Some(value) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+    if let Some(bearer) = value.strip_prefix("Bearer ") {
+        return constant_time_eq(bearer.as_bytes(), token.as_bytes());
+    }
+    let Some(credentials) = value
+        .strip_prefix("Basic ")
+        .and_then(|basic| STANDARD.decode(basic.trim()).ok())
+    else {
+        return false;
+    };
+    credentials
+        .iter()
+        .position(|&byte| byte == b':')
+        .is_some_and(|colon| constant_time_eq(&credentials[colon + 1..], token.as_bytes()))
+}
+
+/// Refuse the `Destination` of a `MOVE` or a `COPY` which would not be accepted as the
+/// path of a request, e.g. one writing to `.git/config`.
+fn check_destination(headers: &HeaderMap, base: &str) -> Result<(), StatusCode> {
+    let Some(destination) = headers.get("destination") else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let uri = destination
+        .to_str()
+        .ok()
+        .and_then(|destination| destination.parse::<Uri>().ok())
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let path = uri.path().strip_prefix(base).unwrap_or(uri.path());
+    if path != PREFIX && !path.starts_with(&format!("{PREFIX}/")) {
+        log::warn!("Refused the WebDAV destination {:?}", uri.path());
+        return Err(StatusCode::FORBIDDEN);
+    }
+    check_path(path)
+}
+
+/// Refuse the paths going outside of the root and the dotfiles, as the static files do,
+/// e.g. `.git`. The dotfiles may still be listed.
+fn check_path(url_path: &str) -> Result<(), StatusCode> {
+    let options = OPTIONS.get().unwrap();
+    let path = percent_decode_str(url_path).decode_utf8_lossy();
+    let path = Path::new(path.strip_prefix(PREFIX).unwrap_or(&path));
+    let relative = path.strip_prefix("/").unwrap_or(path);
+    if relative
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        log::warn!("Refused the WebDAV request for {:?}", path);
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let root = ROOT.get().unwrap();
+    let resolved = root.join(relative);
+    let parent_escapes = resolved
+        .parent()
+        .is_some_and(|parent| parent.starts_with(&*root) && escapes_root(&root, parent));
+    if escapes_root(&root, &resolved) || parent_escapes {
+        log::warn!(
+            "Refused the WebDAV request for {:?} outside of the root",
+            path
+        );
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    if options.show_dotfiles && !options.deny_dotfiles {
+        return Ok(());
+    }
+    let hidden = relative.components().any(|component| match component {
+        Component::Normal(name) => is_hidden(&name.to_string_lossy()),
+        _ => false,
+    });
+    match hidden {
+        true => {
+            log::warn!("Refused the WebDAV request for the dotfile {:?}", path);
+            Err(StatusCode::FORBIDDEN)
+        }
+        false => Ok(()),
+    }
+}
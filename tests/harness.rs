@@ -0,0 +1,43 @@
+use live_server::test::{spawn_temp, spawn_with};
+use reqwest::StatusCode;
+
+#[tokio::test]
+async fn servers_take_turns() {
+    for _ in 0..2 {
+        let server = spawn_temp("./tests/page").await.unwrap();
+        let response = reqwest::get(server.url("/index.js")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        server.stop().await;
+    }
+
+    let (first, second) = tokio::join!(
+        async {
+            let server = spawn_temp("./tests/page").await.unwrap();
+            reqwest::get(server.url("/index.js"))
+                .await
+                .unwrap()
+                .status()
+        },
+        async {
+            let server = spawn_with("./tests/page", |listener| listener.base("/app"))
+                .await
+                .unwrap();
+            reqwest::get(server.url("/index.js"))
+                .await
+                .unwrap()
+                .status()
+        },
+    );
+    assert_eq!(first, StatusCode::OK);
+    assert_eq!(second, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn second_server_of_a_test_fails_fast() {
+    let server = spawn_temp("./tests/page").await.unwrap();
+    assert!(spawn_temp("./tests/page").await.is_err());
+    drop(server);
+    let server = spawn_temp("./tests/page").await.unwrap();
+    let response = reqwest::get(server.url("/index.js")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
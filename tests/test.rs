@@ -1,69 +1,765 @@
-use live_server::listen;
-use reqwest::StatusCode;
+use std::fs;
+use std::time::Duration;
+
+use live_server::{
+    listen, listen_bundle,
+    test::{spawn, spawn_temp, spawn_with},
+    DiskStorage, ListenError, Listener, Storage,
+};
+use reqwest::{Client, StatusCode};
+use sha2::{Digest, Sha256};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
 
 #[tokio::test]
 async fn request() {
-    let listener = listen("127.0.0.1:8000", "./tests/page", true)
-        .await
-        .unwrap();
-    tokio::spawn(async {
-        listener.start().await.unwrap();
-    });
+    let listener = listen("127.0.0.1:0", "./tests/page", true).await.unwrap();
+    let server = spawn(listener).await.unwrap();
 
     // Test requesting index.html
-    let response = reqwest::get("http://127.0.0.1:8000").await.unwrap();
+    let response = reqwest::get(server.url("/")).await.unwrap();
 
     assert_eq!(response.status(), StatusCode::OK);
 
     let content_type = response.headers().get("content-type").unwrap();
-    assert_eq!(content_type, "text/html");
+    assert_eq!(content_type, "text/html; charset=utf-8");
 
     let text = response.text().await.unwrap().replace("\r\n", "\n");
-    let target_text = format!(
-        "{}{}",
-        include_str!("./page/index.html"),
-        format_args!(
-            include_str!("../src/templates/websocket.html"),
-            "127.0.0.1:8000"
-        )
-    )
-    .replace("\r\n", "\n");
+    let script = format!(
+        "<script>\n{}</script>\n",
+        include_str!("../src/templates/client.js")
+            .replace("{{ ws_url }}", "null")
+            .replace("{{ base }}", "")
+    );
+    let target_text = include_str!("./page/index.html")
+        .replace("</body>", &format!("{script}</body>"))
+        .replace("\r\n", "\n");
     assert_eq!(text, target_text);
 
     // Test requesting index.js
-    let response = reqwest::get("http://127.0.0.1:8000/index.js")
-        .await
-        .unwrap();
+    let response = reqwest::get(server.url("/index.js")).await.unwrap();
 
     assert_eq!(response.status(), StatusCode::OK);
 
     let content_type = response.headers().get("content-type").unwrap();
-    assert_eq!(content_type, "application/javascript");
+    assert_eq!(content_type, "application/javascript; charset=utf-8");
 
     let text = response.text().await.unwrap().replace("\r\n", "\n");
     let target_text = include_str!("./page/index.js").replace("\r\n", "\n");
     assert_eq!(text, target_text);
 
     // Test requesting non-existent html file
-    let response = reqwest::get("http://127.0.0.1:8000/404.html")
-        .await
-        .unwrap();
+    let response = reqwest::get(server.url("/404.html")).await.unwrap();
 
     assert_eq!(response.status(), StatusCode::NOT_FOUND);
 
     let content_type = response.headers().get("content-type").unwrap();
-    assert_eq!(content_type, "text/html");
+    assert_eq!(content_type, "text/html; charset=utf-8");
 
     let text = response.text().await.unwrap();
     assert!(text.starts_with("<!DOCTYPE html>"));
 
     // Test requesting non-existent asset
-    let response = reqwest::get("http://127.0.0.1:8000/favicon.ico")
-        .await
-        .unwrap();
+    let response = reqwest::get(server.url("/favicon.ico")).await.unwrap();
 
     assert_eq!(response.status(), StatusCode::NOT_FOUND);
 
     let content_type = response.headers().get("content-type").unwrap();
     assert_eq!(content_type, "image/x-icon");
 }
+
+/// Send the request as it is, since the HTTP clients resolve the `..` of the paths, and
+/// answer the status line and the headers.
+async fn raw_head(addr: std::net::SocketAddr, request: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        head.push(stream.read_u8().await.unwrap());
+    }
+    String::from_utf8(head).unwrap()
+}
+
+async fn raw_status(addr: std::net::SocketAddr, request: &str) -> u16 {
+    raw_head(addr, request).await[9..12].parse().unwrap()
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn escaping_the_root() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("root");
+    fs::create_dir(&root).unwrap();
+    fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+    fs::write(root.join("index.txt"), "index").unwrap();
+    std::os::unix::fs::symlink(dir.path().join("secret.txt"), root.join("link.txt")).unwrap();
+
+    let server = spawn_temp(&root).await.unwrap();
+    let response = reqwest::get(server.url("/index.txt")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = reqwest::get(server.url("/link.txt")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let request = "GET /%2e%2e/secret.txt HTTP/1.1\r\nHost: localhost\r\n\r\n";
+    assert_ne!(raw_status(server.addr(), request).await, 200);
+    server.stop().await;
+
+    let server = spawn_with(&root, |listener| listener.follow_symlinks(true))
+        .await
+        .unwrap();
+    let response = reqwest::get(server.url("/link.txt")).await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "secret");
+}
+
+#[tokio::test]
+async fn dotfiles() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join(".env"), "SECRET=1").unwrap();
+    fs::create_dir(dir.path().join(".git")).unwrap();
+    fs::write(dir.path().join(".git/config"), "[core]").unwrap();
+
+    // Hidden on loopback, unless shown.
+    let server = spawn_temp(dir.path()).await.unwrap();
+    for path in ["/.env", "/.git/config"] {
+        let response = reqwest::get(server.url(path)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+    server.stop().await;
+
+    let server = spawn_with(dir.path(), |listener| listener.show_dotfiles(true))
+        .await
+        .unwrap();
+    let response = reqwest::get(server.url("/.env")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    server.stop().await;
+
+    // Denied when exposed, even if shown.
+    let server = spawn_with(dir.path(), |listener| {
+        listener.show_dotfiles(true).deny_dotfiles(true)
+    })
+    .await
+    .unwrap();
+    for path in ["/.env", "/.git/config"] {
+        let response = reqwest::get(server.url(path)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
+
+#[tokio::test]
+async fn upload() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = Client::new();
+
+    let server = spawn_temp(dir.path()).await.unwrap();
+    let response = client.put(server.url("/new.txt")).body("new").send().await;
+    assert_eq!(response.unwrap().status(), StatusCode::METHOD_NOT_ALLOWED);
+    server.stop().await;
+    assert!(!dir.path().join("new.txt").exists());
+
+    let server = spawn_with(dir.path(), |listener| listener.upload(16))
+        .await
+        .unwrap();
+    let response = client.put(server.url("/new.txt")).body("new").send().await;
+    assert_eq!(response.unwrap().status(), StatusCode::CREATED);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("new.txt")).unwrap(),
+        "new"
+    );
+
+    let response = client
+        .put(server.url("/big.txt"))
+        .body("x".repeat(17))
+        .send();
+    assert_eq!(
+        response.await.unwrap().status(),
+        StatusCode::PAYLOAD_TOO_LARGE
+    );
+    assert!(!dir.path().join("big.txt").exists());
+
+    let response = client
+        .put(server.url("/.env"))
+        .body("SECRET=1")
+        .send()
+        .await;
+    assert_eq!(response.unwrap().status(), StatusCode::FORBIDDEN);
+    assert!(!dir.path().join(".env").exists());
+
+    let request = "PUT /%2e%2e/escaped.txt HTTP/1.1\r\nHost: localhost\r\n\
+        Content-Length: 1\r\n\r\nx";
+    assert_eq!(raw_status(server.addr(), request).await, 400);
+    assert!(!dir.path().parent().unwrap().join("escaped.txt").exists());
+}
+
+#[tokio::test]
+async fn admin_api() {
+    let client = Client::new();
+    let server = spawn_temp("./tests/page").await.unwrap();
+    let response = client.get(server.url("/_live-server/admin/assets")).send();
+    assert_eq!(response.await.unwrap().status(), StatusCode::NOT_FOUND);
+    server.stop().await;
+
+    let server = spawn_with("./tests/page", |listener| listener.admin_token("secret"))
+        .await
+        .unwrap();
+    let url = server.url("/_live-server/admin/assets");
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = client.get(&url).bearer_auth("guess").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = client.get(&url).bearer_auth("secret").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn manage() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a.txt"), "a").unwrap();
+    fs::write(dir.path().join("b.txt"), "b").unwrap();
+    let client = Client::new();
+
+    let server = spawn_with(dir.path(), |listener| listener.admin_token("secret"))
+        .await
+        .unwrap();
+    let response = client.delete(server.url("/_live-server/admin/files/a.txt"));
+    let response = response.bearer_auth("secret").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    server.stop().await;
+
+    let server = spawn_with(dir.path(), |listener| {
+        listener.admin_token("secret").manage(true)
+    })
+    .await
+    .unwrap();
+    let url = server.url("/_live-server/admin/files/a.txt");
+    let response = client.delete(&url).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(dir.path().join("a.txt").exists());
+    let response = client.delete(&url).bearer_auth("secret").send().await;
+    assert_eq!(response.unwrap().status(), StatusCode::NO_CONTENT);
+    assert!(!dir.path().join("a.txt").exists());
+
+    let response = client
+        .patch(server.url("/_live-server/admin/files/b.txt"))
+        .bearer_auth("secret")
+        .header("content-type", "application/json")
+        .body(r#"{"name": "c.txt"}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(dir.path().join("c.txt").exists());
+
+    let response = client
+        .patch(server.url("/_live-server/admin/files/c.txt"))
+        .bearer_auth("secret")
+        .header("content-type", "application/json")
+        .body(r#"{"name": "../c.txt"}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn bundle() {
+    let files = [("/index.html", "<h1>Bundled</h1>")];
+    let listener = listen_bundle("127.0.0.1:0", files).await.unwrap();
+    let server = spawn(listener).await.unwrap();
+
+    let response = reqwest::get(server.url("/")).await.unwrap();
+    assert!(response.text().await.unwrap().contains("<h1>Bundled</h1>"));
+    // Not the files of the working directory.
+    let response = reqwest::get(server.url("/Cargo.toml")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn query_endpoints() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("root");
+    fs::create_dir_all(root.join("docs")).unwrap();
+    fs::write(root.join("docs/a.txt"), "a").unwrap();
+    fs::write(root.join(".env"), "SECRET=1").unwrap();
+    fs::write(dir.path().join("outside.txt"), "outside").unwrap();
+
+    let server = spawn_temp(&root).await.unwrap();
+    let response = reqwest::get(server.url("/_live-server/hash?path=/docs/a.txt")).await;
+    let expected = format!("{:x}  a.txt\n", Sha256::digest(b"a"));
+    assert_eq!(response.unwrap().text().await.unwrap(), expected);
+
+    #[cfg(feature = "listing")]
+    {
+        let response = reqwest::get(server.url("/_live-server/archive?path=/docs")).await;
+        let response = response.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/zip");
+    }
+
+    for query in ["path=/.env", "path=../outside.txt", "path=/missing.txt"] {
+        let url = server.url(&format!("/_live-server/hash?{query}"));
+        let response = reqwest::get(url).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{query}");
+    }
+    #[cfg(feature = "listing")]
+    for query in ["path=..", "path=/missing"] {
+        let url = server.url(&format!("/_live-server/archive?{query}"));
+        let response = reqwest::get(url).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{query}");
+    }
+}
+
+#[cfg(feature = "webdav")]
+#[tokio::test]
+async fn webdav() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("root");
+    fs::create_dir_all(root.join(".git")).unwrap();
+    fs::write(root.join(".git/config"), "[core]").unwrap();
+    fs::write(root.join("a.txt"), "a").unwrap();
+    let client = Client::new();
+    let method = |name: &str| reqwest::Method::from_bytes(name.as_bytes()).unwrap();
+
+    // Read-only without the token.
+    let server = spawn_with(&root, |listener| {
+        listener.webdav(true).admin_token("secret")
+    })
+    .await
+    .unwrap();
+    let url = server.url("/_live-server/webdav/a.txt");
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "a");
+    for request in [
+        client.put(&url).body("changed"),
+        client.delete(&url),
+        client.put(&url).body("changed").bearer_auth("guess"),
+    ] {
+        let response = request.send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+    assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "a");
+
+    let response = client
+        .put(&url)
+        .body("changed")
+        .basic_auth("anyone", Some("secret"));
+    assert!(response.send().await.unwrap().status().is_success());
+    assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "changed");
+
+    // Nor onto the dotfiles or outside of the root.
+    for destination in [
+        server.url("/_live-server/webdav/.git/config"),
+        server.url("/_live-server/webdav/%2e%2e/escaped.txt"),
+        server.url("/elsewhere/a.txt"),
+    ] {
+        let response = client
+            .request(method("MOVE"), &url)
+            .bearer_auth("secret")
+            .header("destination", &destination)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN, "{destination}");
+    }
+    assert_eq!(
+        fs::read_to_string(root.join(".git/config")).unwrap(),
+        "[core]"
+    );
+    assert!(!dir.path().join("escaped.txt").exists());
+
+    let response = client
+        .request(method("MOVE"), &url)
+        .bearer_auth("secret")
+        .header("destination", server.url("/_live-server/webdav/b.txt"))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    assert!(root.join("b.txt").exists());
+    server.stop().await;
+
+    // Without a token, only with the uploads.
+    let server = spawn_with(&root, |listener| listener.webdav(true))
+        .await
+        .unwrap();
+    let url = server.url("/_live-server/webdav/c.txt");
+    let response = client.put(&url).body("c").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    server.stop().await;
+
+    let server = spawn_with(&root, |listener| listener.webdav(true).upload(1024))
+        .await
+        .unwrap();
+    let url = server.url("/_live-server/webdav/c.txt");
+    let response = client.put(&url).body("c").send().await.unwrap();
+    assert!(response.status().is_success());
+}
+
+#[cfg(feature = "listing")]
+#[tokio::test]
+async fn preview_without_disk() {
+    // The bundle has no files on the disk, even in the working directory.
+    let files = [("/index.html", "<h1>Bundled</h1>")];
+    let listener = listen_bundle("127.0.0.1:0", files).await.unwrap();
+    let server = spawn(listener).await.unwrap();
+    for path in ["/Cargo.toml", "/README.md"] {
+        let url = server.url(&format!("/_live-server/preview?path={path}"));
+        let response = reqwest::get(url).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{path}");
+    }
+    server.stop().await;
+
+    // Nor has the zip root, whose directory has other files.
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+    let mut zip = zip::ZipWriter::new(fs::File::create(dir.path().join("site.zip")).unwrap());
+    zip.start_file("index.txt", zip::write::SimpleFileOptions::default())
+        .unwrap();
+    std::io::Write::write_all(&mut zip, b"index").unwrap();
+    zip.finish().unwrap();
+    let listener = listen("127.0.0.1:0", dir.path().join("site.zip"), false)
+        .await
+        .unwrap();
+    let server = spawn(listener).await.unwrap();
+    let response = reqwest::get(server.url("/index.txt")).await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "index");
+    let url = server.url("/_live-server/preview?path=/secret.txt");
+    let response = reqwest::get(url).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[cfg(feature = "listing")]
+#[tokio::test]
+async fn markdown_preview_without_html() {
+    let dir = tempfile::tempdir().unwrap();
+    let markdown = "# Title\n\n<img src=x onerror=alert(1)>\n\n[link](javascript:alert(1))\n";
+    fs::write(dir.path().join("README.md"), markdown).unwrap();
+    let server = spawn_temp(dir.path()).await.unwrap();
+    let url = server.url("/_live-server/preview?path=/README.md");
+    let html = reqwest::get(url).await.unwrap().text().await.unwrap();
+    assert!(html.contains("<h1>Title</h1>"), "{html}");
+    assert!(
+        html.contains("&lt;img src=x onerror=alert(1)&gt;"),
+        "{html}"
+    );
+    assert!(!html.contains("javascript:"), "{html}");
+    server.stop().await;
+}
+
+#[cfg(feature = "listing")]
+#[tokio::test]
+async fn listing_pages() {
+    let dir = tempfile::tempdir().unwrap();
+    for (name, content) in [("a.txt", "1"), ("b.txt", "22"), ("c.txt", "333")] {
+        fs::write(dir.path().join(name), content).unwrap();
+    }
+    fs::create_dir(dir.path().join("z")).unwrap();
+    let server = spawn_temp(dir.path()).await.unwrap();
+    let page = |query: &'static str| {
+        let url = server.url(&format!("/?limit=2&{query}"));
+        async move { reqwest::get(url).await.unwrap().text().await.unwrap() }
+    };
+
+    // The directories first, then the files by name.
+    let html = page("page=1").await;
+    assert!(
+        html.contains(">z</a>") && html.contains(">a.txt</a>"),
+        "{html}"
+    );
+    assert!(!html.contains(">b.txt</a>"), "{html}");
+    let html = page("page=2").await;
+    assert!(
+        html.contains(">b.txt</a>") && html.contains(">c.txt</a>"),
+        "{html}"
+    );
+    assert!(html.contains("<td>3 B</td>"), "{html}");
+    let html = page("sort=size&page=1").await;
+    assert!(
+        html.contains(">c.txt</a>") && !html.contains(">a.txt</a>"),
+        "{html}"
+    );
+    server.stop().await;
+}
+
+#[cfg(feature = "listing")]
+#[tokio::test]
+async fn preview_of_a_large_file() {
+    let dir = tempfile::tempdir().unwrap();
+    // Two bytes per character, with the cut in the middle of one.
+    fs::write(
+        dir.path().join("log.txt"),
+        format!("a{}", "é".repeat(60_000)),
+    )
+    .unwrap();
+    let server = spawn_temp(dir.path()).await.unwrap();
+    let url = server.url("/_live-server/preview?path=/log.txt");
+    let html = reqwest::get(url).await.unwrap().text().await.unwrap();
+    assert!(html.contains("The file is truncated."));
+    assert!(!html.contains('\u{fffd}'));
+    assert!(html.len() < 110 * 1024);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn includes() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join(".env"), "SECRET=1").unwrap();
+    fs::write(dir.path().join("header.html"), "<h1>Header</h1>").unwrap();
+    let page = r#"<!--#include virtual="/header.html" --><!--#include virtual="/.env" -->"#;
+    fs::write(dir.path().join("index.html"), page).unwrap();
+
+    let server = spawn_with(dir.path(), |listener| listener.ssi(true))
+        .await
+        .unwrap();
+    let text = reqwest::get(server.url("/")).await.unwrap().text().await;
+    let text = text.unwrap();
+    assert!(text.contains("<h1>Header</h1>"));
+    assert!(!text.contains("SECRET"));
+}
+
+#[tokio::test]
+async fn env_vars() {
+    std::env::set_var("PUBLIC_LIVE_SERVER_TEST_URL", "http://api.test");
+    std::env::set_var("LIVE_SERVER_TEST_SECRET", "secret");
+    let dir = tempfile::tempdir().unwrap();
+    let page = "{{ env:PUBLIC_LIVE_SERVER_TEST_URL }} {{ env:LIVE_SERVER_TEST_SECRET }}";
+    fs::write(dir.path().join("index.html"), page).unwrap();
+
+    let server = spawn_with(dir.path(), |listener| listener.env_vars(true))
+        .await
+        .unwrap();
+    let text = reqwest::get(server.url("/")).await.unwrap().text().await;
+    let text = text.unwrap();
+    assert!(text.contains("http://api.test"));
+    assert!(text.contains("{{ env:LIVE_SERVER_TEST_SECRET }}"));
+    assert!(!text.contains("secret"));
+}
+
+#[tokio::test]
+async fn trailing_slash_redirect() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("docs")).unwrap();
+    fs::write(dir.path().join("docs/index.html"), "docs").unwrap();
+
+    let server = spawn_temp(dir.path()).await.unwrap();
+    for path in ["/docs", "//docs", "///docs"] {
+        let request = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let head = raw_head(server.addr(), &request).await.to_lowercase();
+        assert!(head.starts_with("http/1.1 301"), "{head}");
+        assert!(head.contains("\r\nlocation: /docs/\r\n"), "{head}");
+    }
+}
+
+#[tokio::test]
+async fn client_log() {
+    let client = Client::new();
+    let error = r#"{"url": "/", "message": "oops\n[2024-01-01T00:00:00Z INFO] forged"}"#;
+    for enabled in [false, true] {
+        let server = spawn_with("./tests/page", |listener| listener.client_log(enabled))
+            .await
+            .unwrap();
+        let response = client
+            .post(server.url("/_live-server/client-log"))
+            .header("content-type", "application/json")
+            .body(error)
+            .send()
+            .await
+            .unwrap();
+        let expected = match enabled {
+            true => StatusCode::NO_CONTENT,
+            false => StatusCode::NOT_FOUND,
+        };
+        assert_eq!(response.status(), expected);
+        server.stop().await;
+    }
+}
+
+#[tokio::test]
+async fn long_polling_replays_the_missed_messages() {
+    let client = Client::new();
+    let server = spawn_temp("./tests/page").await.unwrap();
+    let response = client
+        .get(server.url("/_live-server/poll"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let cursor = response.headers()["x-live-server-cursor"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+
+    // Sent between two requests of the client, which should still get it right away.
+    live_server::reload().await;
+    let response = client
+        .get(server.url(&format!("/_live-server/poll?since={cursor}")))
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(response.headers()["x-live-server-cursor"], cursor.as_str());
+    assert!(response.text().await.unwrap().contains("reload"));
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn invalid_options() {
+    let invalid: [fn(Listener) -> Listener; 4] = [
+        |listener| listener.rewrite("/v1/(", "/latest"),
+        |listener| listener.delay_for("api/[", Duration::from_secs(1)),
+        |listener| listener.channel_capacity(0),
+        |listener| listener.server_header("nginx\n"),
+    ];
+    for configure in invalid {
+        let listener = listen("127.0.0.1:0", "./tests/page", false).await.unwrap();
+        // Fails before serving anything, so without waiting for a turn.
+        let err = configure(listener).start().await.unwrap_err();
+        assert!(
+            matches!(err.downcast_ref(), Some(ListenError::Options(_))),
+            "{err}"
+        );
+    }
+}
+
+#[test]
+fn disk_storage_long_keys() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = DiskStorage::new(dir.path()).unwrap();
+    let long = format!("templates/{}", "é".repeat(300));
+    storage.put("short", b"1").unwrap();
+    storage.put(&long, b"2\n3").unwrap();
+
+    assert_eq!(storage.get(&long).unwrap().as_deref(), Some(&b"2\n3"[..]));
+    assert_eq!(
+        storage.keys("").unwrap(),
+        vec!["short".to_string(), long.clone()]
+    );
+    for entry in fs::read_dir(dir.path()).unwrap() {
+        assert!(entry.unwrap().file_name().len() < 255);
+    }
+    storage.remove(&long).unwrap();
+    assert_eq!(storage.get(&long).unwrap(), None);
+    assert_eq!(storage.keys("templates/").unwrap(), Vec::<String>::new());
+}
+
+#[tokio::test]
+async fn mount_at_the_root() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("mounted.txt"), "mounted").unwrap();
+    let mounted = dir.path().to_path_buf();
+    let server = spawn_with("./tests/page", |listener| listener.mount("/", mounted))
+        .await
+        .unwrap();
+    let response = reqwest::get(server.url("/mounted.txt")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await.unwrap(), "mounted");
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn not_modified() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("index.html"), "<h1>Index</h1>").unwrap();
+    fs::create_dir(dir.path().join("mocks")).unwrap();
+    fs::write(dir.path().join("mocks/users.json"), "[]").unwrap();
+    let mocks = dir.path().join("mocks");
+    let server = spawn_with(dir.path(), |listener| listener.mock("/api", mocks))
+        .await
+        .unwrap();
+    let client = Client::new();
+
+    let mut etags = vec![];
+    for path in ["/", "/api/users"] {
+        let response = client.get(server.url(path)).send().await.unwrap();
+        let etag = response.headers()["etag"].clone();
+        etags.push(etag.clone());
+        let response = client
+            .get(server.url(path))
+            .header("if-none-match", etag.clone())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED, "{path}");
+        assert_eq!(response.headers()["etag"], etag);
+    }
+
+    // Sent again once changed.
+    fs::write(dir.path().join("index.html"), "<h1>Changed</h1>").unwrap();
+    let response = client
+        .get(server.url("/"))
+        .header("if-none-match", etags[0].clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let stats = live_server::stats();
+    assert_eq!(stats.cache_hit_ratio, 2.0 / 3.0);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn virtual_files_of_the_started_listener() {
+    let first = spawn_temp("./tests/page").await.unwrap();
+    // Built while the first server is running, which should not serve its files.
+    let listener = listen("127.0.0.1:0", "./tests/page", false)
+        .await
+        .unwrap()
+        .virtual_file("/second.txt", "second");
+    let response = reqwest::get(first.url("/second.txt")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    first.stop().await;
+
+    let second = spawn(listener).await.unwrap();
+    let response = reqwest::get(second.url("/second.txt")).await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "second");
+    second.stop().await;
+}
+
+#[tokio::test]
+async fn method_not_allowed() {
+    let client = Client::new();
+    for (upload, allow) in [(false, "GET, HEAD"), (true, "GET, HEAD, PUT, POST")] {
+        let dir = tempfile::tempdir().unwrap();
+        let server = spawn_with(dir.path(), |listener| match upload {
+            true => listener.upload(16),
+            false => listener,
+        })
+        .await
+        .unwrap();
+        let mut methods = vec![reqwest::Method::DELETE, reqwest::Method::PATCH];
+        if !upload {
+            methods.extend([reqwest::Method::PUT, reqwest::Method::POST]);
+        }
+        for method in methods {
+            let response = client
+                .request(method.clone(), server.url("/file.txt"))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(
+                response.status(),
+                StatusCode::METHOD_NOT_ALLOWED,
+                "{method}"
+            );
+            assert_eq!(response.headers()["allow"], allow, "{method}");
+        }
+        server.stop().await;
+    }
+}
+
+#[tokio::test]
+async fn large_file_without_extension() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut content = b"\x89PNG\r\n\x1a\n".to_vec();
+    content.resize(3 * 1024 * 1024 + 7, 7);
+    fs::write(dir.path().join("4f2a9c"), &content).unwrap();
+    let server = spawn_temp(dir.path()).await.unwrap();
+    let response = reqwest::get(server.url("/4f2a9c")).await.unwrap();
+    assert_eq!(response.headers()["content-type"], "image/png");
+    // Streamed, with the ETag of its metadata rather than of its content in memory.
+    let etag = response.headers()["etag"].to_str().unwrap().to_owned();
+    assert!(etag.starts_with("W/"), "{etag}");
+    assert_eq!(response.bytes().await.unwrap(), content);
+    server.stop().await;
+}